@@ -0,0 +1,216 @@
+//! Times the Intcode VM on the days that lean on it hardest: interpreted without and with the
+//! decoded instruction cache, and compiled. Run it with `cargo bench --bench intcode`. It
+//! always runs the stand-in programs below, and the real puzzle inputs too if they're in
+//! input/2019.
+
+use advent_of_code::intcode::asm::assemble;
+use advent_of_code::intcode::{parse_program, Backend, State, Vm};
+use std::fs;
+use std::time::Instant;
+
+const RUNS: u32 = 5;
+
+/// Takes the same input as day 9, then spends about half a million instructions on
+/// nested loops with scratch space off the relative base
+const DAY9_STANDIN: &str = "
+        in [mode]
+        arb #stack
+outer:  add #0, #0, [j]
+inner:  mul [i], [j], [rb]
+        add [rb], [sum], [sum]
+        add [j], #1, [j]
+        lt [j], [n], [rb+1]
+        jnz [rb+1], inner
+        add [i], #1, [i]
+        lt [i], [n], [rb+1]
+        jnz [rb+1], outer
+        out [sum]
+        hlt
+mode:   data 0
+n:      data 300
+i:      data 0
+j:      data 0
+sum:    data 0
+stack:  data 0, 0
+";
+
+/// Answers one drone query per run, like day 19's, with a beam between y = x and y = 2x
+const DAY19_STANDIN: &str = "
+        in [x]
+        in [y]
+        lt [y], [x], [t]
+        jnz [t], miss
+        mul [x], #2, [t]
+        lt [t], [y], [t]
+        jnz [t], miss
+        out #1
+        hlt
+miss:   out #0
+        hlt
+x:      data 0
+y:      data 0
+t:      data 0
+";
+
+/// A day 23 NIC. Computer 0 starts a packet going round the ring, and it goes to 255 after
+/// a thousand hops.
+const DAY23_STANDIN: &str = "
+        in [addr]
+        jnz [addr], poll
+        out #1
+        out #0
+        out #0
+poll:   in [x]
+        eq [x], #-1, [t]
+        jnz [t], poll
+        in [y]
+        add [y], [addr], [y]
+        lt [x], #1000, [t]
+        jnz [t], pass
+        out #255
+        out [x]
+        out [y]
+        jz #0, poll
+pass:   add [addr], #1, [t]
+        eq [t], #50, [u]
+        jz [u], send
+        add #0, #0, [t]
+send:   out [t]
+        add [x], #1, [x]
+        out [x]
+        out [y]
+        jz #0, poll
+addr:   data 0
+x:      data 0
+y:      data 0
+t:      data 0
+u:      data 0
+";
+
+#[derive(Debug, Clone, Copy)]
+enum Config {
+    Uncached,
+    Cached,
+    Compiled,
+}
+
+/// Works out a day's answer on one backend
+type Run = fn(&[i64], Config) -> i64;
+
+fn new_vm(tape: &[i64], config: Config) -> Vm {
+    let mut vm = Vm::new(tape);
+    match config {
+        Config::Uncached => vm.set_decode_cache(false),
+        Config::Cached => {}
+        Config::Compiled => vm.set_backend(Backend::Compiled),
+    }
+    vm
+}
+
+fn next_output(vm: &mut Vm) -> i64 {
+    match vm.run().unwrap() {
+        State::Output(out) => out,
+        state => panic!("Expected an output, got {:?}", state),
+    }
+}
+
+/// Day 9 part 2 is one long computation, a few hundred thousand instructions
+fn day9(tape: &[i64], config: Config) -> i64 {
+    let mut vm = new_vm(tape, config);
+    vm.input(2);
+    vm.run_to_halt().unwrap()[0]
+}
+
+/// Day 19 starts the drone program over for every square it asks about
+fn day19(tape: &[i64], config: Config) -> i64 {
+    let mut vm = new_vm(tape, config);
+    let start = vm.snapshot();
+    let mut total = 0;
+    for y in 0..50 {
+        for x in 0..50 {
+            vm.restore(&start);
+            vm.input_all(vec![x, y]);
+            total += next_output(&mut vm);
+        }
+    }
+    total
+}
+
+/// Day 23 runs 50 machines side by side until one of them sends a packet to 255
+fn day23(tape: &[i64], config: Config) -> i64 {
+    let mut vms: Vec<_> = (0..50)
+        .map(|address| {
+            let mut vm = new_vm(tape, config);
+            vm.input(address);
+            vm
+        })
+        .collect();
+
+    loop {
+        for current in 0..vms.len() {
+            loop {
+                match vms[current].run().unwrap() {
+                    State::Output(dest) => {
+                        let x = next_output(&mut vms[current]);
+                        let y = next_output(&mut vms[current]);
+                        if dest == 255 {
+                            return y;
+                        }
+                        vms[dest as usize].input_all(vec![x, y]);
+                    }
+                    State::NeedInput => {
+                        vms[current].input(-1);
+                        break;
+                    }
+                    State::Halted => panic!("NIC {} halted", current),
+                }
+            }
+        }
+    }
+}
+
+/// Runs `run` on every backend, and checks they all get the same answer
+fn time(name: &str, tape: &[i64], run: Run) {
+    let mut answers = Vec::new();
+    for &config in &[Config::Uncached, Config::Cached, Config::Compiled] {
+        let start = Instant::now();
+        let mut answer = 0;
+        for _ in 0..RUNS {
+            answer = run(tape, config);
+        }
+        println!(
+            "{:<15}  {:<8}  {:>12.3?} per run  (answer {})",
+            name,
+            format!("{:?}", config).to_lowercase(),
+            start.elapsed() / RUNS,
+            answer
+        );
+        answers.push(answer);
+    }
+    assert!(
+        answers.windows(2).all(|pair| pair[0] == pair[1]),
+        "{} got different answers",
+        name
+    );
+}
+
+fn main() {
+    let days: [(usize, &str, Run); 3] = [
+        (9, DAY9_STANDIN, day9),
+        (19, DAY19_STANDIN, day19),
+        (23, DAY23_STANDIN, day23),
+    ];
+
+    for &(day, standin, run) in &days {
+        let tape = assemble(standin).unwrap();
+        time(&format!("day {} stand-in", day), &tape, run);
+
+        let path = format!("input/2019/day{}.txt", day);
+        match fs::read_to_string(&path) {
+            Ok(input) if !input.trim().is_empty() => {
+                time(&format!("day {}", day), &parse_program(&input), run)
+            }
+            _ => println!("{:<15}  no input at {}", format!("day {}", day), path),
+        }
+    }
+}
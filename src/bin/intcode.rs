@@ -0,0 +1,162 @@
+use advent_of_code::day25;
+use advent_of_code::intcode::ascii::AsciiMachine;
+use advent_of_code::intcode::debugger::{self, Debugger};
+use advent_of_code::intcode::network::{Nat, Network, Schedule};
+use advent_of_code::intcode::{asm, capture, compile, disasm, parse_program, Vm};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::process;
+
+const USAGE: &str = "usage:
+    intcode disasm <program>    print an annotated listing of a program
+    intcode asm <source>        assemble a listing back into a program
+    intcode debug <program> [input...]
+                                step through a program interactively
+    intcode trace <program> [input...]
+                                print every instruction as it runs
+    intcode check <program> [input...]
+                                run a program on both backends and compare them
+    intcode play <program>      play an ASCII program by typing lines at it
+    intcode adventure <program> play day 25, where n, s, e, w and i are shortcuts
+    intcode capture <program> <file.csv|file.pcap> [packets]
+                                run a day 23 network with a NAT and save its
+                                traffic, stopping after 1000 packets by default";
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Couldn't read {}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+fn load(path: &str) -> Vec<i64> {
+    parse_program(&read(path))
+}
+
+fn parse_input(input: &[&str]) -> Vec<i64> {
+    input
+        .iter()
+        .map(|value| {
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("Input must be numbers, found {:?}", value);
+                process::exit(1);
+            })
+        })
+        .collect()
+}
+
+/// Loads a program and queues up input given on the command line
+fn start(path: &str, input: &[&str]) -> Vm {
+    let mut vm = Vm::new(&load(path));
+    vm.input_all(parse_input(input));
+    vm
+}
+
+/// Runs a program as a 50 node network with a NAT at 255, like day 23, and saves every
+/// packet it sends
+fn capture(path: &str, out: &str, packets: usize) {
+    let mut network = Network::new(&load(path), 50, Schedule::RoundRobin);
+    network.attach(255, Nat::new());
+    let mut seen = 0;
+    if let Err(e) = network.run_until(|_| {
+        seen += 1;
+        seen >= packets
+    }) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    let file = File::create(out).unwrap_or_else(|e| {
+        eprintln!("Couldn't create {}: {}", out, e);
+        process::exit(1);
+    });
+    let file = BufWriter::new(file);
+    let written = if out.ends_with(".pcap") {
+        capture::write_pcap(network.log(), file)
+    } else {
+        capture::write_csv(network.log(), file)
+    };
+    match written {
+        Ok(()) => println!("saved {} packets to {}", network.log().len(), out),
+        Err(e) => {
+            eprintln!("Couldn't write {}: {}", out, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["disasm", path] => print!("{}", disasm::listing(&load(path))),
+        ["asm", path] => match asm::assemble(&read(path)) {
+            Ok(tape) => {
+                let tape: Vec<_> = tape.iter().map(|v| v.to_string()).collect();
+                println!("{}", tape.join(","));
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        ["debug", path, input @ ..] => {
+            let vm = start(path, input);
+            if let Err(e) = debugger::repl(&mut Debugger::new(vm)) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        ["play", path] => {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            let mut machine = AsciiMachine::new(Vm::new(&load(path)));
+            if let Err(e) = machine.play(stdin.lock(), stdout.lock()) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        ["adventure", path] => {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            if let Err(e) = day25::play(&load(path), stdin.lock(), stdout.lock()) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        ["trace", path, input @ ..] => {
+            let mut vm = start(path, input);
+            vm.set_trace_hook(|event| println!("{}", event));
+            match vm.run_until_blocked() {
+                Ok(state) => println!("{:?}, output: {:?}", state, vm.drain_output()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        ["check", path, input @ ..] => {
+            match compile::compare_backends(&load(path), &parse_input(input)) {
+                Ok(outputs) => println!("backends agree, output: {:?}", outputs),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        ["capture", path, out] => capture(path, out, 1000),
+        ["capture", path, out, packets] => match packets.parse() {
+            Ok(packets) => capture(path, out, packets),
+            Err(_) => {
+                eprintln!("Packet count must be a number, found {:?}", packets);
+                process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
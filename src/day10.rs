@@ -1,161 +1,189 @@
-use std::collections::{BTreeMap, HashSet};
-use std::ops::Index;
+use crate::grid::{Grid, GridError, Pos, Tile};
+use num::Integer;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
-struct AsteroidMap {
-    map: Vec<MapValue>,
-    width: usize,
-    _height: usize,
+pub struct AsteroidMap {
+    grid: Grid<MapValue>,
 }
 
-struct AsteroidMapIter<'a> {
-    map: &'a AsteroidMap,
-    index: usize,
+#[derive(PartialEq, Eq, Debug)]
+enum MapValue {
+    Empty,
+    Asteroid,
 }
 
-impl AsteroidMap {
-    fn iter(&self) -> AsteroidMapIter {
-        AsteroidMapIter {
-            map: self,
-            index: 0,
+impl Tile for MapValue {
+    fn from_char(c: char) -> Option<MapValue> {
+        match c {
+            '.' => Some(MapValue::Empty),
+            '#' => Some(MapValue::Asteroid),
+            _ => None,
         }
     }
-}
-
-impl<'a> Iterator for AsteroidMapIter<'a> {
-    type Item = (usize, usize, &'a MapValue);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let x = self.index % self.map.width;
-        let y = self.index / self.map.width;
-        let result = Some((x, y, self.map.map.get(self.index)?));
-        self.index += 1;
-        result
+    fn to_char(&self) -> char {
+        match self {
+            MapValue::Empty => '.',
+            MapValue::Asteroid => '#',
+        }
     }
 }
 
-impl Index<(usize, usize)> for AsteroidMap {
-    type Output = MapValue;
+/// The way from one asteroid to another, divided down so everything on the same line of
+/// sight has the same direction. `y` goes down the map, like the input.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+struct Direction {
+    dx: i64,
+    dy: i64,
+}
 
-    fn index(&self, pos: (usize, usize)) -> &Self::Output {
-        &self.map[pos.1 * self.width + pos.0]
+impl Direction {
+    fn between(from: (usize, usize), to: (usize, usize)) -> Direction {
+        let dx = to.0 as i64 - from.0 as i64;
+        let dy = to.1 as i64 - from.1 as i64;
+        let gcd = dx.gcd(&dy);
+        Direction {
+            dx: dx / gcd,
+            dy: dy / gcd,
+        }
+    }
+
+    /// Whether this is in the half of the circle from straight up round to just before
+    /// straight down
+    fn right_half(self) -> bool {
+        self.dx > 0 || (self.dx == 0 && self.dy < 0)
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
-enum MapValue {
-    Empty,
-    Asteroid,
+/// Orders directions clockwise, starting from straight up
+impl Ord for Direction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.right_half().cmp(&self.right_half()).then_with(|| {
+            // in the same half, a positive cross product means `other` is clockwise of us
+            let cross = self.dx * other.dy - self.dy * other.dx;
+            0.cmp(&cross)
+        })
+    }
 }
 
-#[aoc_generator(day10, part1)]
-fn parse_asteroid_map(input: &str) -> AsteroidMap {
-    AsteroidMap {
-        map: input
-            .lines()
-            .flat_map(|line| {
-                line.chars()
-                    .map(|c| match c {
-                        '.' => MapValue::Empty,
-                        '#' => MapValue::Asteroid,
-                        e => panic!("Unrecognized map value {:?}", e),
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect(),
-        width: input.lines().next().unwrap().len(),
-        _height: input.lines().count(),
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-#[aoc(day10, part1)]
-fn solve_p1(map: &AsteroidMap) -> usize {
-    let mut max_visible = 0usize;
+impl AsteroidMap {
+    /// `#` for an asteroid and `.` for empty space, one row per line
+    pub fn parse(input: &str) -> Result<AsteroidMap, GridError> {
+        Ok(AsteroidMap {
+            grid: Grid::parse(input)?,
+        })
+    }
 
-    for (station_x, station_y, station_value) in map.iter() {
-        if *station_value == MapValue::Empty {
-            continue;
-        }
-        let mut angles = HashSet::new();
-        'a: for (other_x, other_y, other_value) in map.iter() {
-            if *other_value == MapValue::Empty {
-                continue 'a;
-            }
-            if station_x == other_x && station_y == other_y {
-                continue;
-            }
-
-            let dx = other_x as isize - station_x as isize;
-            let dy = other_y as isize - station_y as isize;
-
-            angles.insert((f64::atan2(dy as f64, dx as f64) * 1000.0) as i64);
+    pub fn asteroids(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.grid
+            .iter()
+            .filter(|(_, value)| **value == MapValue::Asteroid)
+            .map(|(pos, _)| pos)
+    }
+
+    /// How many other asteroids can be seen from `station`
+    pub fn visible_from(&self, station: (usize, usize)) -> usize {
+        self.asteroids()
+            .filter(|&other| other != station)
+            .map(|other| Direction::between(station, other))
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// Every other asteroid, in the order a laser at `station` destroys them
+    ///
+    /// ```
+    /// use advent_of_code::day10::AsteroidMap;
+    ///
+    /// let map = AsteroidMap::parse("#.#\n.##\n#.#").unwrap();
+    /// let order: Vec<_> = map.vaporization_order((0, 2)).collect();
+    /// assert_eq!(order, vec![(0, 0), (1, 1), (2, 1), (2, 2), (2, 0)]);
+    /// ```
+    pub fn vaporization_order(&self, station: (usize, usize)) -> Vaporization {
+        let mut rays = BTreeMap::new();
+        for other in self.asteroids().filter(|&other| other != station) {
+            rays.entry(Direction::between(station, other))
+                .or_insert_with(Vec::new)
+                .push(other);
         }
 
-        if angles.len() > max_visible {
-            max_visible = angles.len();
+        let distance = |pos: &(usize, usize)| {
+            (pos.0 as i64 - station.0 as i64).abs() + (pos.1 as i64 - station.1 as i64).abs()
+        };
+        Vaporization {
+            rays: rays
+                .into_values()
+                .map(|mut ray| {
+                    ray.sort_by_key(distance);
+                    ray.into()
+                })
+                .collect(),
+            next: 0,
         }
     }
 
-    max_visible
+    /// The asteroid that can see the most others, and how many it sees
+    pub fn best_station(&self) -> Option<((usize, usize), usize)> {
+        self.asteroids()
+            .map(|station| (station, self.visible_from(station)))
+            .max_by_key(|&(_, visible)| visible)
+    }
 }
 
-#[aoc_generator(day10, part2)]
-fn p2_generator(input: &str) -> AsteroidMap {
-    parse_asteroid_map(input)
+/// The laser goes clockwise from straight up, destroying the closest asteroid on each line
+/// of sight it passes. The ones behind get hit on later turns.
+pub struct Vaporization {
+    /// What's left on each line of sight, closest first, in the order the laser gets to them
+    rays: Vec<VecDeque<(usize, usize)>>,
+    next: usize,
 }
 
-#[aoc(day10, part2)]
-fn solve_p2(map: &AsteroidMap) -> usize {
-    let mut best_angles = BTreeMap::new();
-
-    for (station_x, station_y, station_value) in map.iter() {
-        if *station_value == MapValue::Empty {
-            continue;
-        }
-        let mut angles = BTreeMap::new();
-        'a: for (other_x, other_y, other_value) in map.iter() {
-            if *other_value == MapValue::Empty {
-                continue 'a;
-            }
-            if station_x == other_x && station_y == other_y {
-                continue;
-            }
-
-            let dx = other_x as isize - station_x as isize;
-            let dy = other_y as isize - station_y as isize;
-
-            // We're converting to i64 (after multiplying by 10) because f64 doesn't implement
-            let angle = (f64::atan2(dy as f64, dx as f64).to_degrees() * 10.0) as i64;
-
-            // We want to start at 90 degrees, and go clockwise, not counterclockwise
+impl Iterator for Vaporization {
+    type Item = (usize, usize);
 
-            angles
-                .entry(angle)
-                .or_insert_with(Vec::new)
-                .push((other_x, other_y));
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rays.is_empty() {
+            return None;
         }
-
-        if angles.len() > best_angles.len() {
-            best_angles = angles;
+        self.next %= self.rays.len();
+        let hit = self.rays[self.next].pop_front();
+        if self.rays[self.next].is_empty() {
+            // everything after moves down one, so `next` already points at the next ray
+            self.rays.remove(self.next);
+        } else {
+            self.next += 1;
         }
+        hit
     }
+}
 
-    // So now, start at -900, and increase it
-    let mut counter = 0;
-    let mut vec = best_angles.into_iter().collect::<Vec<_>>();
+#[aoc_generator(day10, part1)]
+fn parse_asteroid_map(input: &str) -> AsteroidMap {
+    AsteroidMap::parse(input).unwrap()
+}
 
-    // Start at -90 degrees
-    let mut i = vec.iter().position(|(angle, _)| *angle >= -900).unwrap();
-    // We already got t
-    while counter < 199 {
-        vec[i].1.remove(0);
-        i += 1;
-        i %= vec.len();
-        counter += 1;
-    }
+#[aoc(day10, part1)]
+fn solve_p1(map: &AsteroidMap) -> usize {
+    map.best_station().map_or(0, |(_, visible)| visible)
+}
 
-    let pos = vec[i].1[0];
+#[aoc_generator(day10, part2)]
+fn p2_generator(input: &str) -> AsteroidMap {
+    parse_asteroid_map(input)
+}
 
-    pos.0 * 100 + pos.1
+#[aoc(day10, part2)]
+fn solve_p2(map: &AsteroidMap) -> usize {
+    let (station, _) = map.best_station().unwrap();
+    let (x, y) = map.vaporization_order(station).nth(199).unwrap();
+    x * 100 + y
 }
 
 #[cfg(test)]
@@ -195,4 +223,99 @@ mod tests {
 ";
         assert_eq!(solve_p1(&parse_asteroid_map(input)), 35);
     }
+
+    #[test]
+    fn directions() {
+        let dir = |dx, dy| Direction { dx, dy };
+        assert_eq!(Direction::between((3, 3), (9, 7)), dir(3, 2));
+        assert_eq!(Direction::between((3, 3), (3, 0)), dir(0, -1));
+
+        // clockwise from up, with y going down
+        let mut dirs = vec![
+            dir(-1, -1),
+            dir(-1, 0),
+            dir(0, 1),
+            dir(1, 0),
+            dir(1, -1),
+            dir(0, -1),
+            dir(-1, 1),
+            dir(1, 1),
+        ];
+        dirs.sort();
+        assert_eq!(
+            dirs,
+            vec![
+                dir(0, -1),
+                dir(1, -1),
+                dir(1, 0),
+                dir(1, 1),
+                dir(0, 1),
+                dir(-1, 1),
+                dir(-1, 0),
+                dir(-1, -1),
+            ]
+        );
+
+        // far enough apart that rounding an angle would put them together
+        assert!(dir(999, 998) < dir(1000, 999));
+        assert_ne!(
+            Direction::between((0, 0), (1000, 999)),
+            Direction::between((0, 0), (999, 998))
+        );
+    }
+
+    #[test]
+    fn day10_large() {
+        let map = parse_asteroid_map(LARGE);
+        assert_eq!(map.best_station(), Some(((11, 13), 210)));
+        assert_eq!(solve_p2(&map), 802);
+    }
+
+    #[test]
+    fn vaporization() {
+        let map = parse_asteroid_map(LARGE);
+        let order: Vec<_> = map.vaporization_order((11, 13)).collect();
+        assert_eq!(order.len(), map.asteroids().count() - 1);
+        for &(n, pos) in &[
+            (1, (11, 12)),
+            (2, (12, 1)),
+            (3, (12, 2)),
+            (10, (12, 8)),
+            (20, (16, 0)),
+            (50, (16, 9)),
+            (100, (10, 16)),
+            (199, (9, 6)),
+            (200, (8, 2)),
+            (201, (10, 9)),
+            (299, (11, 1)),
+        ] {
+            assert_eq!(order[n - 1], pos, "asteroid {}", n);
+        }
+
+        // (2, 0) is behind (1, 1), so it has to wait for the second turn
+        let map = parse_asteroid_map("#.#\n.##\n#.#");
+        let order: Vec<_> = map.vaporization_order((0, 2)).collect();
+        assert_eq!(order, vec![(0, 0), (1, 1), (2, 1), (2, 2), (2, 0)]);
+    }
+
+    const LARGE: &str = ".#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##";
 }
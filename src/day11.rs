@@ -1,4 +1,5 @@
-use crate::day9::{intcode_computer, parse_program};
+use crate::day9::parse_program;
+use crate::intcode::{State, Vm};
 use std::collections::HashMap;
 
 #[aoc_generator(day11, part1)]
@@ -58,9 +59,9 @@ enum Color {
     White,
 }
 
-impl Into<i64> for Color {
-    fn into(self) -> i64 {
-        match self {
+impl From<Color> for i64 {
+    fn from(color: Color) -> i64 {
+        match color {
             Color::Black => 0,
             Color::White => 1,
         }
@@ -85,22 +86,23 @@ fn solve_p1(tape: &[i64]) -> usize {
 
     let mut panels: HashMap<(i64, i64), Color> = HashMap::new();
 
-    let mut tape = tape.to_owned();
+    let mut vm = Vm::new(tape);
 
-    let mut i = 0;
-    let mut relative_base = 0;
     loop {
-        let paint = intcode_computer(&mut tape, &mut i, &mut relative_base, || {
-            (*panels.get(&(robot_x, robot_y)).unwrap_or(&Color::Black)).into()
-        });
+        vm.input((*panels.get(&(robot_x, robot_y)).unwrap_or(&Color::Black)).into());
 
-        if paint == -1 {
-            break;
-        }
+        let paint = match vm.run().unwrap() {
+            State::Output(paint) => paint,
+            State::Halted => break,
+            State::NeedInput => panic!("Robot wants input before painting"),
+        };
 
         panels.insert((robot_x, robot_y), paint.into());
 
-        let dir = intcode_computer(&mut tape, &mut i, &mut relative_base, || 0);
+        let dir = match vm.run().unwrap() {
+            State::Output(dir) => dir,
+            state => panic!("Robot painted, but didn't turn: {:?}", state),
+        };
 
         match dir {
             0 => robot_dir = robot_dir.rotate_left(),
@@ -130,23 +132,23 @@ fn solve_p2(tape: &[i64]) -> usize {
 
     panels.insert((robot_x, robot_y), Color::White);
 
-    let mut tape = tape.to_owned();
-
-    let mut i = 0;
-    let mut relative_base = 0;
+    let mut vm = Vm::new(tape);
 
     loop {
-        let paint = intcode_computer(&mut tape, &mut i, &mut relative_base, || {
-            (*panels.get(&(robot_x, robot_y)).unwrap_or(&Color::Black)).into()
-        });
+        vm.input((*panels.get(&(robot_x, robot_y)).unwrap_or(&Color::Black)).into());
 
-        if paint == -1 {
-            break;
-        }
+        let paint = match vm.run().unwrap() {
+            State::Output(paint) => paint,
+            State::Halted => break,
+            State::NeedInput => panic!("Robot wants input before painting"),
+        };
 
         panels.insert((robot_x, robot_y), paint.into());
 
-        let dir = intcode_computer(&mut tape, &mut i, &mut relative_base, || 1);
+        let dir = match vm.run().unwrap() {
+            State::Output(dir) => dir,
+            state => panic!("Robot painted, but didn't turn: {:?}", state),
+        };
 
         match dir {
             0 => robot_dir = robot_dir.rotate_left(),
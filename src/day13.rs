@@ -1,4 +1,5 @@
-use crate::day9::{intcode_computer, parse_program};
+use crate::day9::parse_program;
+use crate::intcode::{State, Vm};
 use std::collections::HashMap;
 
 #[aoc_generator(day13)]
@@ -8,26 +9,15 @@ fn day13_gen(input: &str) -> Vec<i64> {
 
 #[aoc(day13, part1)]
 fn solve_p1(tape: &[i64]) -> usize {
-    let mut tape = tape.to_owned();
+    let output = Vm::new(tape).run_to_halt().unwrap();
 
     let mut screen = HashMap::new();
 
-    let mut i = 0;
-    let mut relative_base = 0;
-    loop {
-        let x = intcode_computer(&mut tape, &mut i, &mut relative_base, || 0);
-
-        if x == -1 {
-            break;
-        }
-
-        let y = intcode_computer(&mut tape, &mut i, &mut relative_base, || 0);
-        let tile_type = intcode_computer(&mut tape, &mut i, &mut relative_base, || 0);
-
+    for tile in output.chunks(3) {
         screen
-            .entry(tile_type)
+            .entry(tile[2])
             .or_insert_with(Vec::new)
-            .push((x, y));
+            .push((tile[0], tile[1]));
     }
 
     screen.get(&2).unwrap().len()
@@ -35,12 +25,14 @@ fn solve_p1(tape: &[i64]) -> usize {
 
 #[aoc(day13, part2)]
 fn solve_p2(tape: &[i64]) -> i64 {
-    let mut tape = tape.to_owned();
+    let mut vm = Vm::new(tape);
 
-    tape[0] = 2;
+    vm.poke(0, 2).unwrap(); // play for free
 
     let mut screen = HashMap::new();
 
+    // `show_game` is only ever set by hand, with RUSTFLAGS="--cfg show_game"
+    #[allow(unexpected_cfgs)]
     fn get_move(screen: &HashMap<(i64, i64), i64>) -> i64 {
         let mut paddle_pos = 0;
         let mut ball_pos = 0;
@@ -73,27 +65,22 @@ fn solve_p2(tape: &[i64]) -> i64 {
         (ball_pos - paddle_pos).signum()
     }
 
-    let mut i = 0;
-    let mut relative_base = 0;
-
     let mut answer = 0;
 
     loop {
-        let x = intcode_computer(&mut tape, &mut i, &mut relative_base, || get_move(&screen));
-        let y = intcode_computer(&mut tape, &mut i, &mut relative_base, || get_move(&screen));
-        let tile_type =
-            intcode_computer(&mut tape, &mut i, &mut relative_base, || get_move(&screen));
+        let mut triple = [0; 3];
+        for out in triple.iter_mut() {
+            match vm.run_with(|| get_move(&screen)).unwrap() {
+                State::Output(o) => *out = o,
+                _ => return answer,
+            }
+        }
 
+        let [x, y, tile_type] = triple;
         if x == -1 && y == 0 {
             answer = tile_type;
         }
 
-        if tile_type == -1 {
-            break;
-        }
-
         screen.insert((x, y), tile_type);
     }
-
-    answer
 }
@@ -118,7 +118,7 @@ fn solve_p2(rxns: &[Reaction]) -> u64 {
 }
 
 fn ceil_div(x: u64, y: u64) -> u64 {
-    x / y + if x % y != 0 { 1 } else { 0 }
+    x.div_ceil(y)
 }
 
 #[cfg(test)]
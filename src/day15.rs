@@ -1,5 +1,7 @@
 use crate::day11::Direction;
-use crate::day9::{intcode_computer, parse_program};
+use crate::day9::parse_program;
+use crate::grid::{SparseGrid, Tile};
+use crate::intcode::{State, Vm};
 use std::collections::{HashMap, HashSet, VecDeque};
 
 #[aoc_generator(day15)]
@@ -19,9 +21,9 @@ impl From<i64> for Direction {
     }
 }
 
-impl Into<i64> for Direction {
-    fn into(self) -> i64 {
-        match self {
+impl From<Direction> for i64 {
+    fn from(direction: Direction) -> i64 {
+        match direction {
             Direction::Up => 1,
             Direction::Down => 2,
             Direction::Left => 3,
@@ -38,92 +40,82 @@ enum TileType {
     Robot,
 }
 
+impl Tile for TileType {
+    fn from_char(c: char) -> Option<TileType> {
+        match c {
+            ' ' => Some(TileType::Empty),
+            '█' => Some(TileType::Wall),
+            '▣' => Some(TileType::Goal),
+            '●' => Some(TileType::Robot),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        match self {
+            TileType::Empty => ' ',
+            TileType::Wall => '█',
+            TileType::Goal => '▣',
+            TileType::Robot => '●',
+        }
+    }
+}
+
 struct SeachResult(
-    HashMap<(i64, i64), TileType>,
+    SparseGrid<TileType>,
     HashMap<(i64, i64), (i64, i64)>,
     (i64, i64),
 );
 
+/// Asks the droid to move in a direction, returning its status code
+fn move_droid(vm: &mut Vm, dir: i64) -> i64 {
+    vm.input(dir);
+    match vm.run().unwrap() {
+        State::Output(status) => status,
+        state => panic!("Droid didn't report back: {:?}", state),
+    }
+}
+
+/// Explores the whole area breadth first. Every open square remembers the droid's state
+/// from when it got there, so exploring from it is just a matter of restoring that.
 fn intcode_search(tape: &[i64]) -> SeachResult {
-    let mut tape = tape.to_owned();
-    let mut i = 0;
-    let mut rb = 0;
+    let mut vm = Vm::new(tape);
 
-    let mut current = (0, 0);
     let mut goal = (0, 0);
 
-    let mut map = HashMap::new();
+    let mut map = SparseGrid::new();
     let mut parents = HashMap::new();
+    let mut queue = VecDeque::new();
 
-    map.insert(current, TileType::Robot);
-
-    loop {
-        let mut moved = false;
+    map.insert((0, 0), TileType::Robot);
+    queue.push_back(((0, 0), vm.snapshot()));
 
+    while let Some((current, state)) = queue.pop_front() {
         for dir in 1..=4 {
             let new_pos = (
                 current.0 + Direction::from(dir).dx(),
                 current.1 + Direction::from(dir).dy(),
             );
-            if map.contains_key(&new_pos) {
+            if map.contains(new_pos) {
                 continue;
             }
-            let result = intcode_computer(&mut tape, &mut i, &mut rb, || dir);
-            match result {
-                0 => {
-                    map.insert(new_pos, TileType::Wall);
-                }
-                1 => {
-                    parents.insert(new_pos, current);
-                    map.insert(current, TileType::Empty);
-                    current = new_pos;
-                    map.insert(current, TileType::Robot);
-                    moved = true;
-                    break;
-                }
+
+            vm.restore(&state);
+            let tile = match move_droid(&mut vm, dir) {
+                0 => TileType::Wall,
+                1 => TileType::Empty,
                 2 => {
-                    parents.insert(new_pos, current);
-                    map.insert(current, TileType::Empty);
-                    current = new_pos;
-                    map.insert(current, TileType::Goal);
-                    goal = current;
+                    goal = new_pos;
+                    TileType::Goal
                 }
                 e => panic!("Unrecognized response: {:?}", e),
-            }
-        }
+            };
 
-        // if we didn't move, go back to the parent
-        if !moved {
-            let back = parents.get(&current).unwrap();
-
-            let dx = back.0 - current.0;
-            let dy = back.1 - current.1;
-            let dir: i64 = match (dx, dy) {
-                (1, 0) => Direction::Right,
-                (-1, 0) => Direction::Left,
-                (0, 1) => Direction::Down,
-                (0, -1) => Direction::Up,
-                e => panic!("Impossible (dx, dy): {:?}", e),
-            }
-            .into();
-            //println!("Moving back to {:?}, {:?}, {:?}, {:?}", back, dx, dy, dir);
-            let result = intcode_computer(&mut tape, &mut i, &mut rb, || dir);
-
-            // if the result isn't 1, something is wrong
-            assert_eq!(result, 1);
-            // don't overwrite the goal when backtracking
-            if map[&current] != TileType::Goal {
-                map.insert(current, TileType::Empty);
+            if tile != TileType::Wall {
+                parents.insert(new_pos, current);
+                queue.push_back((new_pos, vm.snapshot()));
             }
-            current = *back;
-            if map[&current] != TileType::Goal {
-                map.insert(current, TileType::Robot);
-            }
-        }
-
-        if current == (0, 0) {
-            // we've backtracked all the way back to the start, so we're done
-            break;
+            map.insert(new_pos, tile);
         }
     }
 
@@ -134,25 +126,7 @@ fn intcode_search(tape: &[i64]) -> SeachResult {
 fn solve_p1(tape: &[i64]) -> usize {
     let SeachResult(map, parents, goal) = intcode_search(tape);
 
-    for y in -21..20 {
-        for x in -30..25 {
-            let tile = map.get(&(x, y)).unwrap_or(&TileType::Empty);
-            if y == 0 && x == 0 {
-                print!("0");
-            } else {
-                print!(
-                    "{}",
-                    match tile {
-                        TileType::Empty => " ",
-                        TileType::Wall => "█",
-                        TileType::Robot => "●",
-                        TileType::Goal => "▣",
-                    }
-                )
-            }
-        }
-        println!();
-    }
+    print!("{}", map);
 
     let mut current = goal;
     let mut distance = 0;
@@ -178,12 +152,8 @@ fn solve_p2(tape: &[i64]) -> usize {
         while queue_size > 0 {
             let current = queue.pop_back().unwrap();
             // add each of it's children to the queue
-            for dir in 1..=4 {
-                let child = (
-                    current.0 + Direction::from(dir).dx(),
-                    current.1 + Direction::from(dir).dy(),
-                );
-                if map[&child] != TileType::Wall && !visited.contains(&child) {
+            for child in map.neighbours4(current) {
+                if map[child] != TileType::Wall && !visited.contains(&child) {
                     visited.insert(child);
                     queue.push_front(child);
                 }
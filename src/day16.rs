@@ -70,7 +70,7 @@ fn solve_p2(digits: &[i32]) -> i32 {
         .iter()
         .cycle()
         .take(digits.len() * 10_000)
-        .map(|x| *x)
+        .copied()
         .collect::<Vec<_>>();
 
     let orig_len = output.len();
@@ -87,7 +87,7 @@ fn solve_p2(digits: &[i32]) -> i32 {
                 next_ouput.push((*x + next_ouput[next_ouput.len() - 1]).abs() % 10);
             }
         }
-        output = next_ouput.iter().rev().map(|x| *x).collect();
+        output = next_ouput.iter().rev().copied().collect();
     }
 
     let mut offset = 0;
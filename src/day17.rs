@@ -1,14 +1,10 @@
 use crate::day11::Direction;
-use crate::day9::{intcode_computer, parse_program};
+use crate::day9::parse_program;
+use crate::grid::{Grid, Pos, Tile};
+use crate::intcode::ascii::AsciiMachine;
+use crate::intcode::Vm;
 use std::char;
-use std::collections::{HashMap, HashSet};
-
-pub const DIRECTIONS: [Direction; 4] = [
-    Direction::Up,
-    Direction::Down,
-    Direction::Left,
-    Direction::Right,
-];
+use std::collections::HashSet;
 
 #[aoc_generator(day17)]
 fn day17_gen(input: &str) -> Vec<i64> {
@@ -21,169 +17,82 @@ enum TileType {
     Scaffold,
 }
 
-#[aoc(day17, part1)]
-fn solve_p1(tape: &[i64]) -> usize {
-    let mut tape = tape.to_owned();
-    let mut i = 0;
-    let mut rb = 0;
-
-    let mut map = HashMap::new();
-    let mut current = (0usize, 0usize);
-    let mut total_alignment = 0;
-
-    loop {
-        let output = intcode_computer(&mut tape, &mut i, &mut rb, || 0);
-        if output == -1 {
-            break;
+impl Tile for TileType {
+    fn from_char(c: char) -> Option<TileType> {
+        match c {
+            '.' => Some(TileType::Empty),
+            // the robot is always on the scaffold
+            '#' | '^' | 'v' | '<' | '>' => Some(TileType::Scaffold),
+            _ => None,
         }
-        let output = output as u8 as char;
-        match output {
-            '.' => {
-                map.insert(current, TileType::Empty);
-                current.0 += 1;
-            }
-            '#' | '^' | 'v' | '<' | '>' => {
-                map.insert(current, TileType::Scaffold);
-                current.0 += 1;
-            }
-            '\n' => {
-                current.0 = 0;
-                current.1 += 1;
-            }
-            e => panic!("Unknown char: {:?}", e),
-        };
-        print!("{}", output);
     }
 
-    let width = map.keys().max_by_key(|(x, _)| x).unwrap().0 + 1;
-    let height = map.keys().max_by_key(|(_, y)| y).unwrap().1 + 1;
-
-    //println!("{:?} by {:?}", width, height);
-
-    for x in 0..width {
-        for y in 0..height {
-            if x > 0
-                && x < width - 1
-                && y > 0
-                && y < height - 1
-                && map[&(x, y)] == TileType::Scaffold
-                && map[&(x + 1, y)] == TileType::Scaffold
-                && map[&(x - 1, y)] == TileType::Scaffold
-                && map[&(x, y + 1)] == TileType::Scaffold
-                && map[&(x, y - 1)] == TileType::Scaffold
-            {
-                total_alignment += x * y
-            }
+    fn to_char(&self) -> char {
+        match self {
+            TileType::Empty => '.',
+            TileType::Scaffold => '#',
         }
     }
-
-    total_alignment
 }
 
-#[aoc(day17, part2)]
-fn solve_p2(tape: &[i64]) -> i64 {
-    let mut tape = tape.to_owned();
-    tape[0] = 2;
-    let mut i = 0;
-    let mut rb = 0;
-
-    let mut map = HashMap::new();
-    let mut robot_loc = (0, 0);
-    let mut robot_dir = Direction::Up;
+/// Every bit of scaffold that has scaffold on all four sides
+fn intersections(map: &Grid<TileType>) -> impl Iterator<Item = Pos> + '_ {
+    let scaffold = move |pos: &Pos| map[*pos] == TileType::Scaffold;
+    map.iter()
+        .map(|(pos, _)| pos)
+        .filter(move |pos| scaffold(pos) && map.neighbours4(*pos).filter(scaffold).count() == 4)
+}
 
-    {
-        // get map
-        let mut current = (0usize, 0usize);
+#[aoc(day17, part1)]
+fn solve_p1(tape: &[i64]) -> usize {
+    let output = Vm::new(tape).run_to_halt().unwrap();
 
-        loop {
-            let output = intcode_computer(&mut tape, &mut i, &mut rb, || 0);
-            if output == -1 {
-                break;
-            }
-            let output = output as u8 as char;
-            match output {
-                '.' => {
-                    map.insert(current, TileType::Empty);
-                    current.0 += 1;
-                }
-                '#' => {
-                    map.insert(current, TileType::Scaffold);
-                    current.0 += 1;
-                }
-                '^' | 'v' | '<' | '>' => {
-                    map.insert(current, TileType::Scaffold);
-                    robot_loc = current;
-                    robot_dir = match output {
-                        '^' => Direction::Up,
-                        'v' => Direction::Down,
-                        '>' => Direction::Right,
-                        '<' => Direction::Left,
-                        _ => unreachable!(),
-                    };
-                    current.0 += 1;
-                }
-                '\n' => {
-                    current.0 = 0;
-                    current.1 += 1;
-                }
-                'M' => {
-                    // This means that it's asking for "Main"
-                    break;
-                }
-                e => eprintln!("Unknown char: {:?}", e),
-            };
-        }
-    }
+    let view: String = output.into_iter().map(|c| c as u8 as char).collect();
+    print!("{}", view);
+    let map: Grid<TileType> = Grid::parse(view.trim()).unwrap();
 
-    let width = map.keys().max_by_key(|(x, _)| x).unwrap().0 + 1;
-    let height = map.keys().max_by_key(|(_, y)| y).unwrap().1 + 1;
+    intersections(&map).map(|(x, y)| x * y).sum()
+}
 
-    let intersections = {
-        let mut intersections = HashSet::new();
-        for x in 0..width {
-            for y in 0..height {
-                if x > 0
-                    && x < width - 1
-                    && y > 0
-                    && y < height - 1
-                    && map[&(x, y)] == TileType::Scaffold
-                    && map[&(x + 1, y)] == TileType::Scaffold
-                    && map[&(x - 1, y)] == TileType::Scaffold
-                    && map[&(x, y + 1)] == TileType::Scaffold
-                    && map[&(x, y - 1)] == TileType::Scaffold
-                {
-                    intersections.insert((x, y));
-                }
-            }
-        }
-        intersections
+#[aoc(day17, part2)]
+fn solve_p2(tape: &[i64]) -> i64 {
+    let mut vm = Vm::new(tape);
+    vm.poke(0, 2).unwrap(); // wake up the robot
+    let mut robot = AsciiMachine::new(vm);
+
+    // the map comes first, then the robot asks for its main routine
+    let screen = robot.read_until_prompt().unwrap();
+    let view: Grid<char> = Grid::parse(screen.text.split("Main").next().unwrap().trim()).unwrap();
+    let map = view.map(|&c| TileType::from_char(c).unwrap());
+
+    let mut robot_loc = view
+        .position(|c| "^v<>".contains(*c))
+        .expect("No robot on the map");
+    let mut robot_dir = match view[robot_loc] {
+        '^' => Direction::Up,
+        'v' => Direction::Down,
+        '>' => Direction::Right,
+        '<' => Direction::Left,
+        _ => unreachable!(),
     };
 
+    let intersections: HashSet<_> = intersections(&map).collect();
+
     let mut path = Vec::new();
     path.push(robot_loc);
 
     {
         // get path
         loop {
-            let mut neighbors = Vec::new();
-            for dir in &DIRECTIONS {
-                let new_pos = (
-                    (robot_loc.0 as i64 + dir.dx()),
-                    (robot_loc.1 as i64 + dir.dy()),
-                );
-                if new_pos.0 >= 0 && new_pos.1 >= 0 {
-                    let new_pos = (new_pos.0 as usize, new_pos.1 as usize);
-                    if new_pos.0 < width
-                        && new_pos.1 < height
-                        && map[&new_pos] == TileType::Scaffold
-                        && (!path.contains(&new_pos) || intersections.contains(&new_pos))
-                    {
-                        neighbors.push(new_pos);
-                    }
-                }
-            }
-
-            if neighbors.len() == 0 {
+            let neighbors: Vec<_> = map
+                .neighbours4(robot_loc)
+                .filter(|pos| {
+                    map[*pos] == TileType::Scaffold
+                        && (!path.contains(pos) || intersections.contains(pos))
+                })
+                .collect();
+
+            if neighbors.is_empty() {
                 break;
             }
             if neighbors.len() == 1 {
@@ -195,11 +104,10 @@ fn solve_p2(tape: &[i64]) -> i64 {
                 let prev = path[path.len() - 2];
                 let dx = robot_loc.0 as i64 - prev.0 as i64;
                 let dy = robot_loc.1 as i64 - prev.1 as i64;
-                let new_pos = (
-                    (robot_loc.0 as i64 + dx) as usize,
-                    (robot_loc.1 as i64 + dy) as usize,
-                );
-                assert!(neighbors.contains(&new_pos));
+                let new_pos = map
+                    .offset(robot_loc, (dx, dy))
+                    .filter(|pos| neighbors.contains(pos))
+                    .expect("Can't go straight through the intersection");
                 path.push(new_pos);
                 robot_loc = new_pos;
             }
@@ -223,7 +131,7 @@ fn solve_p2(tape: &[i64]) -> i64 {
         // if the direction changed...
         if dx != robot_dir.dx() || dy != robot_dir.dy() {
             // the first instruction won't be a forward
-            if instructions.len() > 0 {
+            if !instructions.is_empty() {
                 if in_this_dir < 10 {
                     instructions.push(Instruction::F(in_this_dir));
                 } else {
@@ -260,70 +168,42 @@ fn solve_p2(tape: &[i64]) -> i64 {
     C = R12 L6 L6 L8
          */
 
-    {
-        let mut sequences = HashMap::new();
-        for start in 0..instructions.len() {
-            for end in start..=instructions.len() {
-                // The length can be at  most 10, and we assume it's at least 2
-                if end - start > 10 || end - start < 2 {
-                    continue;
-                }
-                let seq = &instructions[start..end];
-                sequences
-                    .entry(seq.to_owned())
-                    .and_modify(|x: &mut Vec<(usize, usize)>| x.push((start, end)))
-                    .or_insert(vec![(start, end)]);
-            }
-        }
-
-        'outer: for (a, a_regions) in sequences.iter() {
-            for (b, b_regions) in sequences.iter() {
-                if a == b {
-                    continue;
-                }
-                for (c, c_regions) in sequences.iter() {
-                    if b == c {
-                        continue;
-                    }
-                    for pos in 0..instructions.len() {
-                        // check if this position is covered by a, b, or c
-                        let mut pos_valid = false;
-                        for range in a_regions.iter().chain(b_regions).chain(c_regions) {
-                            if pos >= range.0 && pos < range.1 {
-                                pos_valid = true;
-                                break;
-                            }
-                        }
-
-                        if !pos_valid {
-                            break;
-                        }
+    let main = "A,B,B,A,B,C,A,C,B,C";
+    let a = "L,4,L,6,L,8,L,6,6";
+    let b = "L,8,R,6,6,L,6,6";
+    let c = "R,6,6,L,6,L,6,L,8";
+    // no video feed
+    let video = "n";
 
-                        // THIS IS A VALID sequence that covers every section!!!
-                        println!("{:?} {:?} {:?}", a, b, c);
-                        break 'outer;
-                    }
-                }
-            }
-        }
+    for line in &[main, a, b, c, video] {
+        robot.send_line(line);
     }
+    robot
+        .read_until_prompt()
+        .unwrap()
+        .value()
+        .expect("Robot stopped without reporting dust")
+}
 
-    let main = "A,B,B,A,B,C,A,C,B,C\n".bytes();
-    let a = "L,4,L,6,L,8,L,6,6\n".bytes();
-    let b = "L,8,R,6,6,L,6,6\n".bytes();
-    let c = "R,6,6,L,6,L,6,L,8\n".bytes();
-
-    let end = "n\n".bytes();
-
-    let mut iter = main.chain(a).chain(b).chain(c).chain(end);
-
-    loop {
-        let result = intcode_computer(&mut tape, &mut i, &mut rb, || iter.next().unwrap() as i64);
-
-        if result >= 127 {
-            return result;
-        }
-
-        //print!("{}", result as u8 as char);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alignment() {
+        let view = "
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^.."
+            .trim();
+        let map = Grid::parse(view).unwrap();
+        let mut found: Vec<_> = intersections(&map).collect();
+        found.sort();
+        assert_eq!(found, vec![(2, 2), (2, 4), (6, 4), (10, 4)]);
+        assert_eq!(found.iter().map(|(x, y)| x * y).sum::<usize>(), 76);
     }
 }
@@ -1,4 +1,4 @@
-use crate::day17::DIRECTIONS;
+use crate::grid::{Grid, Pos, Tile};
 use std::collections::HashMap;
 use std::fmt;
 
@@ -11,74 +11,70 @@ enum TileType {
     Door(u8),
 }
 
+impl Tile for TileType {
+    fn from_char(c: char) -> Option<TileType> {
+        match c {
+            '@' => Some(TileType::Player),
+            '.' => Some(TileType::Empty),
+            '#' => Some(TileType::Wall),
+            'A'..='Z' => Some(TileType::Door(c as u8 - b'A')),
+            'a'..='z' => Some(TileType::Key(c as u8 - b'a')),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        match *self {
+            TileType::Player => '@',
+            TileType::Empty => '.',
+            TileType::Wall => '#',
+            TileType::Door(a) => (a + b'A') as char,
+            TileType::Key(a) => (a + b'a') as char,
+        }
+    }
+}
+
 #[derive(Clone)]
 struct TunnelMap {
-    map: HashMap<(usize, usize), TileType>,
-    width: usize,
-    height: usize,
-    player_pos: (usize, usize),
+    grid: Grid<TileType>,
+    player_pos: Pos,
     keys: i32,
 }
 
+impl TunnelMap {
+    /// Every key in the rectangle between two corners, as a bitmask
+    fn keys_between(&self, from: Pos, to: Pos) -> i32 {
+        self.grid
+            .iter()
+            .filter(|((x, y), _)| (from.0..=to.0).contains(x) && (from.1..=to.1).contains(y))
+            .map(|(_, tile)| match tile {
+                TileType::Key(a) => 1 << a,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
 impl fmt::Debug for TunnelMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                write!(
-                    f,
-                    "{}",
-                    match self.map[&(x, y)] {
-                        TileType::Player => '▣',
-                        TileType::Empty => ' ',
-                        TileType::Wall => '█',
-                        TileType::Door(a) => (a + 'A' as u8) as char,
-                        TileType::Key(a) => (a + 'a' as u8) as char,
-                    }
-                )?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        write!(f, "{}", self.grid)
     }
 }
 
 #[aoc_generator(day18)]
 fn parse_map(input: &str) -> TunnelMap {
-    let mut map = HashMap::new();
-    let mut player_pos = (0, 0);
-    let mut keys = 0i32;
-
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
-
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let tile_type = match c {
-                '@' => {
-                    player_pos = (x, y);
-                    TileType::Player
-                }
-                '.' => TileType::Empty,
-                '#' => TileType::Wall,
-                c if c.is_uppercase() => TileType::Door(c as u8 - 'A' as u8),
-                c if c.is_lowercase() => {
-                    let idx = c as u8 - 'a' as u8;
-                    keys += 1 << (idx as u32);
-                    TileType::Key(idx)
-                }
-                c => panic!("Unrecognized {:?}", c),
-            };
-            map.insert((x, y), tile_type);
-        }
-    }
+    let grid = Grid::parse(input).unwrap();
+    let player_pos = grid
+        .position(|&tile| tile == TileType::Player)
+        .expect("No player on the map");
 
-    TunnelMap {
-        map,
-        width,
-        height,
+    let mut map = TunnelMap {
+        grid,
         player_pos,
-        keys,
-    }
+        keys: 0,
+    };
+    map.keys = map.keys_between((0, 0), (map.grid.width(), map.grid.height()));
+    map
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone)]
@@ -112,10 +108,10 @@ fn count_steps(map: &TunnelMap) -> usize {
     let mut queue = VecDeque::new();
     let mut steps_to = HashMap::new();
 
-    queue.push_back(State::new(&map));
-    steps_to.insert(State::new(&map), 0);
+    queue.push_back(State::new(map));
+    steps_to.insert(State::new(map), 0);
     // so we start at
-    while queue.len() > 0 {
+    while !queue.is_empty() {
         let current = queue.pop_front().unwrap();
         if current.missing_keys == 0 {
             return steps_to[&current];
@@ -123,15 +119,10 @@ fn count_steps(map: &TunnelMap) -> usize {
         if !steps_to.contains_key(&current) {
             continue;
         }
-        for dir in &DIRECTIONS {
-            let new_pos = (
-                (current.pos.0 as i64 + dir.dx()) as usize,
-                (current.pos.1 as i64 + dir.dy()) as usize,
-            );
-
-            let next_state = match map.map.get(&new_pos) {
-                Some(TileType::Wall) => continue,
-                Some(TileType::Door(a)) => {
+        for new_pos in map.grid.neighbours4(current.pos) {
+            let next_state = match map.grid[new_pos] {
+                TileType::Wall => continue,
+                TileType::Door(a) => {
                     if (current.missing_keys >> a) & 1 == 0 {
                         State {
                             missing_keys: current.missing_keys,
@@ -141,14 +132,14 @@ fn count_steps(map: &TunnelMap) -> usize {
                         continue;
                     }
                 }
-                Some(TileType::Key(a)) => State {
+                TileType::Key(a) => State {
                     missing_keys: match (current.missing_keys >> a) & 1 {
                         1 => current.missing_keys ^ (1 << a),
                         _ => current.missing_keys,
                     },
                     pos: new_pos,
                 },
-                Some(TileType::Player) | Some(TileType::Empty) | None => State {
+                TileType::Player | TileType::Empty => State {
                     missing_keys: current.missing_keys,
                     pos: new_pos,
                 },
@@ -173,74 +164,31 @@ fn solve_p1(map: &TunnelMap) -> usize {
 
 #[aoc(day18, part2)]
 fn solve_p2(map: &TunnelMap) -> usize {
-    let mut new_map = map.clone();
+    let (x, y) = map.player_pos;
+    let mut split = map.clone();
+    for &pos in &[(x, y), (x, y + 1), (x, y - 1), (x + 1, y), (x - 1, y)] {
+        split.grid[pos] = TileType::Wall;
+    }
 
-    new_map.map.insert(map.player_pos, TileType::Wall);
-    new_map
-        .map
-        .insert((map.player_pos.0, map.player_pos.1 + 1), TileType::Wall);
-    new_map
-        .map
-        .insert((map.player_pos.0, map.player_pos.1 - 1), TileType::Wall);
-    new_map
-        .map
-        .insert((map.player_pos.0 + 1, map.player_pos.1), TileType::Wall);
-    new_map
-        .map
-        .insert((map.player_pos.0 - 1, map.player_pos.1), TileType::Wall);
-    new_map.map.insert(
-        (map.player_pos.0 + 1, map.player_pos.1 + 1),
-        TileType::Player,
-    );
-    new_map.map.insert(
-        (map.player_pos.0 + 1, map.player_pos.1 - 1),
-        TileType::Player,
-    );
-    new_map.map.insert(
-        (map.player_pos.0 - 1, map.player_pos.1 + 1),
-        TileType::Player,
-    );
-    new_map.map.insert(
-        (map.player_pos.0 - 1, map.player_pos.1 - 1),
-        TileType::Player,
-    );
+    let (right, bottom) = (map.grid.width() - 1, map.grid.height() - 1);
+    let quadrants = [
+        ((x + 1, y + 1), (x, y), (right, bottom)),
+        ((x + 1, y - 1), (x, 0), (right, y)),
+        ((x - 1, y + 1), (0, y), (x, bottom)),
+        ((x - 1, y - 1), (0, 0), (x, y)),
+    ];
 
     let mut total = 0;
-    for quadrant in 0..4 {
-        let quadrant_map: HashMap<(usize, usize), TileType> = new_map
-            .map
-            .clone()
-            .into_iter()
-            .filter(|&(k, _v)| match quadrant {
-                0 => k.0 >= map.player_pos.0 && k.1 >= map.player_pos.1,
-                1 => k.0 >= map.player_pos.0 && k.1 <= map.player_pos.1,
-                2 => k.0 <= map.player_pos.0 && k.1 >= map.player_pos.1,
-                3 => k.0 <= map.player_pos.0 && k.1 <= map.player_pos.1,
-                _ => unreachable!(),
-            })
-            .collect();
-
-        let keys = quadrant_map
-            .iter()
-            .map(|(_k, v)| if let TileType::Key(a) = v { 1 << a } else { 0 })
-            .sum();
-
-        let map1 = TunnelMap {
-            map: quadrant_map,
-            width: map.width / 2, // these don't actually work because the printing code goes from 0..width
-            height: map.height / 2,
-            player_pos: match quadrant {
-                0 => (map.player_pos.0 + 1, map.player_pos.1 + 1),
-                1 => (map.player_pos.0 + 1, map.player_pos.1 - 1),
-                2 => (map.player_pos.0 - 1, map.player_pos.1 + 1),
-                3 => (map.player_pos.0 - 1, map.player_pos.1 - 1),
-                _ => unreachable!(),
-            },
-            keys,
+    for &(start, from, to) in &quadrants {
+        split.grid[start] = TileType::Player;
+        // each robot is walled into its own quadrant, and the others will open any doors
+        // whose keys aren't in it
+        let quadrant = TunnelMap {
+            player_pos: start,
+            keys: split.keys_between(from, to),
+            ..split.clone()
         };
-
-        let steps = count_steps(&map1);
-        total += steps;
+        total += count_steps(&quadrant);
     }
 
     total
@@ -290,4 +238,29 @@ mod tests {
             .trim();
         assert_eq!(81, solve_p1(&parse_map(input)));
     }
+
+    #[test]
+    fn day18_test4() {
+        let input = "
+#######
+#a.#Cd#
+##...##
+##.@.##
+##...##
+#cB#Ab#
+#######"
+            .trim();
+        assert_eq!(8, solve_p2(&parse_map(input)));
+
+        let input = "
+###############
+#d.ABC.#.....a#
+######...######
+######.@.######
+######...######
+#b.....#.....c#
+###############"
+            .trim();
+        assert_eq!(24, solve_p2(&parse_map(input)));
+    }
 }
@@ -1,4 +1,5 @@
-use crate::day9::{intcode_computer, parse_program};
+use crate::day9::parse_program;
+use crate::intcode::{Backend, Snapshot, State, Vm};
 
 #[aoc_generator(day19)]
 fn day19_gen(input: &str) -> Vec<i64> {
@@ -7,10 +8,11 @@ fn day19_gen(input: &str) -> Vec<i64> {
 
 #[aoc(day19, part1)]
 fn solve_p1(tape: &[i64]) -> usize {
+    let mut drone = Drone::new(tape);
     let mut total = 0;
     for y in 0..50 {
         for x in 0..50 {
-            match query(tape, x, y) {
+            match drone.query(x, y) {
                 0 => continue,
                 1 => total += 1,
                 _ => unreachable!(),
@@ -21,17 +23,31 @@ fn solve_p1(tape: &[i64]) -> usize {
     total
 }
 
-fn query(tape: &[i64], x: i64, y: i64) -> i64 {
-    let mut first = false;
-    let mut tape = tape.to_owned();
-    intcode_computer(&mut tape, &mut 0, &mut 0, || {
-        if first {
-            y
-        } else {
-            first = true;
-            x
+/// Every query needs a fresh drone program, so start each one from a snapshot of the
+/// program before it ran instead of copying the tape. The program gets run thousands of
+/// times, so it's worth compiling.
+struct Drone {
+    vm: Vm,
+    start: Snapshot,
+}
+
+impl Drone {
+    fn new(tape: &[i64]) -> Drone {
+        let mut vm = Vm::new(tape);
+        vm.set_backend(Backend::Compiled);
+        let start = vm.snapshot();
+        Drone { vm, start }
+    }
+
+    fn query(&mut self, x: i64, y: i64) -> i64 {
+        self.vm.restore(&self.start);
+        self.vm.input(x);
+        self.vm.input(y);
+        match self.vm.run().unwrap() {
+            State::Output(out) => out,
+            state => panic!("Drone didn't report back: {:?}", state),
         }
-    })
+    }
 }
 
 #[aoc(day19, part2)]
@@ -39,13 +55,14 @@ fn solve_p2(tape: &[i64]) -> i64 {
     // Find a point (x, y) such that
     //     (x + 100, y) is in the beam
     //     (x, y + 100) is in the beam
+    let mut drone = Drone::new(tape);
     let mut x = 0;
     let mut y = 0;
 
     let mut highest_y_found = None;
 
     loop {
-        match query(tape, x, y) {
+        match drone.query(x, y) {
             0 => {
                 if let Some(highest_y_found) = highest_y_found {
                     if y == highest_y_found {
@@ -70,7 +87,7 @@ fn solve_p2(tape: &[i64]) -> i64 {
             }
             1 => {
                 highest_y_found = Some(y);
-                if query(tape, x + 99, y) == 1 && query(tape, x, y + 99) == 1 {
+                if drone.query(x + 99, y) == 1 && drone.query(x, y + 99) == 1 {
                     break x * 10_000 + y;
                 }
                 x += 1;
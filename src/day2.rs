@@ -1,33 +1,18 @@
+use crate::intcode::{Backend, Vm};
+
 #[aoc_generator(day2, part1)]
-pub fn parse_program(input: &str) -> Vec<usize> {
-    input
-        .split(',')
-        .map(|i| i.parse::<usize>().unwrap())
-        .collect()
+pub fn parse_program(input: &str) -> Vec<i64> {
+    crate::intcode::parse_program(input)
 }
 
-fn run_tape(mut tape: Vec<usize>) -> Vec<usize> {
-    let mut i = 0;
-    loop {
-        match tape[i] {
-            1 => {
-                let out = tape[i + 3];
-                tape[out] = tape[tape[i + 2]] + tape[tape[i + 1]]
-            }
-            2 => {
-                let out = tape[i + 3];
-                tape[out] = tape[tape[i + 2]] * tape[tape[i + 1]]
-            }
-            99 => break,
-            _ => {}
-        }
-        i += 4;
-    }
-    tape
+fn run_tape(tape: Vec<i64>) -> Vec<i64> {
+    let mut vm = Vm::new(&tape);
+    vm.run_to_halt().unwrap();
+    vm.into_tape()
 }
 
 #[aoc(day2, part1)]
-fn solve_p1(tape: &[usize]) -> usize {
+fn solve_p1(tape: &[i64]) -> i64 {
     let mut tape = tape.to_owned();
     tape[1] = 12;
     tape[2] = 2;
@@ -35,18 +20,23 @@ fn solve_p1(tape: &[usize]) -> usize {
 }
 
 #[aoc_generator(day2, part2)]
-fn p2_generator(input: &str) -> Vec<usize> {
+fn p2_generator(input: &str) -> Vec<i64> {
     parse_program(input)
 }
 
 #[aoc(day2, part2)]
-fn solve_p2(tape: &[usize]) -> usize {
+fn solve_p2(tape: &[i64]) -> i64 {
+    let mut vm = Vm::new(tape);
+    vm.set_backend(Backend::Compiled);
+    let start = vm.snapshot();
+
     for noun in 1..99 {
         for verb in 1..99 {
-            let mut tape = tape.to_owned();
-            tape[1] = noun;
-            tape[2] = verb;
-            if run_tape(tape)[0] == 19_690_720 {
+            vm.restore(&start);
+            vm.poke(1, noun).unwrap();
+            vm.poke(2, verb).unwrap();
+            vm.run_to_halt().unwrap();
+            if vm.peek(0) == 19_690_720 {
                 return noun * 100 + verb;
             }
         }
@@ -1,107 +1,90 @@
-use crate::day17::DIRECTIONS;
+use crate::grid::{Grid, Pos, Tile as GridTile};
 use std::collections::{HashMap, VecDeque};
 
+#[derive(Clone, Copy)]
 enum Tile {
     Wall,
     Passage,
     Portal(char, char),
+    /// Outside the maze, or part of a portal's label
+    Void,
 }
 
-type Pos = (usize, usize);
+impl GridTile for Tile {
+    fn from_char(c: char) -> Option<Tile> {
+        match c {
+            '#' => Some(Tile::Wall),
+            '.' => Some(Tile::Passage),
+            ' ' => Some(Tile::Void),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        match *self {
+            Tile::Wall => '#',
+            Tile::Passage => '.',
+            Tile::Portal(a, _) => a,
+            Tile::Void => ' ',
+        }
+    }
+}
 
 struct Maze {
-    map: HashMap<Pos, Tile>,
-    width: usize,
-    height: usize,
+    map: Grid<Tile>,
     portals: HashMap<(char, char), (Pos, Pos)>,
 }
 
 #[aoc_generator(day20)]
 fn parse_maze(input: &str) -> Maze {
-    let mut map = HashMap::new();
     let mut portals: HashMap<(char, char), (Pos, Pos)> = HashMap::new();
 
-    let width = input.find('\n').unwrap();
-    let height = input.lines().count();
-
-    let input = input
+    // editors like to trim the spaces off the ends of lines, so pad them back out
+    let lines = input
         .lines()
         .map(|line| line.chars().collect::<Vec<_>>())
         .collect::<Vec<_>>();
+    let width = lines.iter().map(Vec::len).max().unwrap();
+    let height = lines.len();
+    let input = Grid::from_fn(width, height, |(x, y)| {
+        lines[y].get(x).copied().unwrap_or(' ')
+    });
 
-    println!("{:?} by {:?}", input[0].len(), input.len());
+    println!("{:?} by {:?}", width, height);
 
-    for y in 1..input.len() - 1 {
-        for x in 1..input[0].len() - 1 {
-            let c = input[y][x];
-            match c {
-                '#' => {
-                    map.entry((x, y)).or_insert(Tile::Wall);
-                }
-                '.' => {
-                    map.entry((x, y)).or_insert(Tile::Passage);
-                }
-                c if c.is_uppercase() => {
-                    let neighbours = [
-                        (input[y - 1][x], y - 1, x),
-                        (input[y + 1][x], y + 1, x),
-                        (input[y][x - 1], y, x - 1),
-                        (input[y][x + 1], y, x + 1),
-                    ];
-                    if let Some((_empty, passage_y, passage_x)) =
-                        neighbours.iter().find(|&(empty, _y, _x)| *empty == '.')
-                    {
-                        let other_letter = neighbours
-                            .iter()
-                            .find(|&(other, _y, _x)| other.is_uppercase())
-                            .unwrap();
-                        let name = if (x == other_letter.2 && y > other_letter.1)
-                            || (y == other_letter.1 && x > other_letter.2)
-                        {
-                            (other_letter.0, c)
-                        } else {
-                            (c, other_letter.0)
-                        };
-                        map.insert((*passage_x, *passage_y), Tile::Portal(name.0, name.1));
-
-                        if *passage_x == 2
-                            || *passage_y == 2
-                            || *passage_x > width - 5
-                            || *passage_y > height - 5
-                        {
-                            portals.entry(name).or_default().1 = (*passage_x, *passage_y);
-                        } else {
-                            portals.entry(name).or_default().0 = (*passage_x, *passage_y);
-                        }
-                    };
-                    continue;
-                }
-                _ => continue,
+    let mut map = input.map(|&c| Tile::from_char(c).unwrap_or(Tile::Void));
+    for (pos, &c) in input.iter() {
+        if !c.is_uppercase() {
+            continue;
+        }
+        let neighbours: Vec<_> = input.neighbours4(pos).map(|n| (input[n], n)).collect();
+        if let Some(&(_, passage)) = neighbours.iter().find(|(next, _)| *next == '.') {
+            let &(other, other_pos) = neighbours
+                .iter()
+                .find(|(other, _)| other.is_uppercase())
+                .unwrap();
+            // labels read top to bottom or left to right
+            let name = if other_pos < pos {
+                (other, c)
+            } else {
+                (c, other)
+            };
+            map[passage] = Tile::Portal(name.0, name.1);
+
+            if passage.0 == 2 || passage.1 == 2 || passage.0 > width - 5 || passage.1 > height - 5 {
+                portals.entry(name).or_default().1 = passage;
+            } else {
+                portals.entry(name).or_default().0 = passage;
             }
         }
     }
 
-    Maze {
-        map,
-        width,
-        height,
-        portals,
-    }
+    Maze { map, portals }
 }
 
 #[aoc(day20, part1)]
 fn solve_p1(maze: &Maze) -> usize {
-    for y in 0..maze.height {
-        for x in 0..maze.width {
-            match maze.map.get(&(x, y)) {
-                Some(Tile::Wall) => print!("#"),
-                Some(Tile::Passage) => print!("."),
-                Some(Tile::Portal(a, _b)) => print!("{}", a),
-                None => print!(" "),
-            }
-        }
-        println!();
-    }
+    print!("{}", maze.map);
 
     let mut queue = VecDeque::new();
     let mut steps_to = HashMap::new();
@@ -112,7 +95,7 @@ fn solve_p1(maze: &Maze) -> usize {
     queue.push_back(start);
     steps_to.insert(start, 0);
     // so we start at
-    while queue.len() > 0 {
+    while !queue.is_empty() {
         let current = queue.pop_front().unwrap();
 
         if current == goal {
@@ -123,8 +106,8 @@ fn solve_p1(maze: &Maze) -> usize {
             continue;
         }
 
-        if let Some(Tile::Portal(a, b)) = maze.map.get(&current) {
-            let portal = maze.portals.get(&(*a, *b)).unwrap();
+        if let Tile::Portal(a, b) = maze.map[current] {
+            let portal = maze.portals[&(a, b)];
             if current == portal.0 {
                 let new_pos = portal.1;
                 if !steps_to.contains_key(&new_pos) {
@@ -135,23 +118,14 @@ fn solve_p1(maze: &Maze) -> usize {
             }
         }
 
-        for dir in &DIRECTIONS {
-            let new_pos = (
-                (current.0 as i64 + dir.dx()) as usize,
-                (current.1 as i64 + dir.dy()) as usize,
-            );
-
-            if new_pos.0 > maze.width || new_pos.1 > maze.height {
-                continue;
-            }
-
+        for new_pos in maze.map.neighbours4(current) {
             if steps_to.contains_key(&new_pos) {
                 continue;
             }
 
-            match maze.map.get(&new_pos) {
-                None | Some(Tile::Wall) => continue,
-                Some(Tile::Passage) | Some(Tile::Portal(_, _)) => {
+            match maze.map[new_pos] {
+                Tile::Void | Tile::Wall => continue,
+                Tile::Passage | Tile::Portal(_, _) => {
                     let steps = steps_to[&current] + 1;
                     steps_to.insert(new_pos, steps);
                     queue.push_back(new_pos);
@@ -175,7 +149,7 @@ fn solve_p2(maze: &Maze) -> usize {
     queue.push_back(start);
     steps_to.insert(start, 0);
 
-    while queue.len() > 0 {
+    while !queue.is_empty() {
         let current = queue.pop_front().unwrap();
 
         if current == goal {
@@ -188,8 +162,8 @@ fn solve_p2(maze: &Maze) -> usize {
 
         let (current_pos, current_level) = current;
 
-        if let Some(Tile::Portal(a, b)) = maze.map.get(&current_pos) {
-            let portal = maze.portals.get(&(*a, *b)).unwrap();
+        if let Tile::Portal(a, b) = maze.map[current_pos] {
+            let portal = maze.portals[&(a, b)];
             if current_pos == portal.0 {
                 // we recurse into a level deeper
                 let new_pos = (portal.1, current_level + 1);
@@ -208,26 +182,15 @@ fn solve_p2(maze: &Maze) -> usize {
             }
         }
 
-        for dir in &DIRECTIONS {
-            let new_pos = (
-                (
-                    (current_pos.0 as i64 + dir.dx()) as usize,
-                    (current_pos.1 as i64 + dir.dy()) as usize,
-                ),
-                current_level,
-            );
-
-            if (new_pos.0).0 > maze.width || (new_pos.0).1 > maze.height {
-                continue;
-            }
-
+        for new_pos in maze.map.neighbours4(current_pos) {
+            let new_pos = (new_pos, current_level);
             if steps_to.contains_key(&new_pos) {
                 continue;
             }
 
-            match maze.map.get(&new_pos.0) {
-                None | Some(Tile::Wall) => continue,
-                Some(Tile::Passage) | Some(Tile::Portal(_, _)) => {
+            match maze.map[new_pos.0] {
+                Tile::Void | Tile::Wall => continue,
+                Tile::Passage | Tile::Portal(_, _) => {
                     let steps = steps_to[&current] + 1;
                     steps_to.insert(new_pos, steps);
                     queue.push_back(new_pos);
@@ -285,6 +248,6 @@ RE....#.#                           #......RF
                A O F   N                     
                A A D   M                     "[1..],
         );
-        println!("{:?}", solve_p2(&maze));
+        assert_eq!(solve_p2(&maze), 396);
     }
 }
@@ -1,4 +1,6 @@
-use crate::day9::{intcode_computer, parse_program};
+use crate::day9::parse_program;
+use crate::intcode::ascii::AsciiMachine;
+use crate::intcode::Vm;
 
 #[aoc_generator(day21)]
 fn day21_gen(input: &str) -> Vec<i64> {
@@ -52,24 +54,12 @@ RUN
 }
 
 fn test_program(tape: &[i64], program: &str) -> Option<i64> {
-    let mut program_feed = program.bytes();
+    let mut droid = AsciiMachine::new(Vm::new(tape));
+    droid.send(program);
 
-    let mut tape = tape.to_owned();
-    let mut i = 0;
-    let mut rb = 0;
-
-    loop {
-        let result = intcode_computer(&mut tape, &mut i, &mut rb, || {
-            program_feed.next().unwrap() as i64
-        });
-
-        if result == -1 {
-            return None;
-        }
-
-        if result > 127 {
-            return Some(result);
-        }
-        print!("{}", result as u8 as char);
+    let screen = droid.read_until_prompt().unwrap();
+    if screen.values.is_empty() {
+        print!("{}", screen.text);
     }
+    screen.value()
 }
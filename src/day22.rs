@@ -1,120 +1,299 @@
-#[derive(Debug)]
-enum Instruction {
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
     Reverse,
     Interleave(usize),
     Cut(isize),
 }
 
-#[aoc_generator(day22)]
-fn parse_shuffle(input: &str) -> Vec<Instruction> {
-    input
-        .lines()
-        .map(|line| {
-            if line == "deal into new stack" {
-                return Instruction::Reverse;
-            } else if line.starts_with("deal with increment") {
-                Instruction::Interleave(line.split(' ').last().unwrap().parse().unwrap())
-            } else if line.starts_with("cut") {
-                Instruction::Cut(line.split(' ').last().unwrap().parse().unwrap())
-            } else {
-                panic!("Unknown line: {:?}", line);
-            }
-        })
-        .collect()
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Instruction, String> {
+        let line = line.trim();
+        let bad = |n: &str| format!("bad number {:?}", n);
+        if line == "deal into new stack" {
+            Ok(Instruction::Reverse)
+        } else if let Some(n) = line.strip_prefix("deal with increment ") {
+            n.parse().map(Instruction::Interleave).map_err(|_| bad(n))
+        } else if let Some(n) = line.strip_prefix("cut ") {
+            n.parse().map(Instruction::Cut).map_err(|_| bad(n))
+        } else {
+            Err(format!("unknown instruction {:?}", line))
+        }
+    }
 }
 
-#[aoc(day22, part1)]
-fn solve_p1(shuffle: &[Instruction]) -> usize {
-    let mut deck = (0..10007).collect::<Vec<usize>>();
-    for ins in shuffle {
-        match ins {
-            Instruction::Reverse => {
-                deck[..].reverse();
-            }
-            Instruction::Cut(amount) => {
-                // 4674
-                // 3460 + (10007 - 4674)
-                let n = if *amount > 0 {
-                    *amount as usize
-                } else {
-                    (deck.len() as isize - amount.abs()) as usize
-                };
-                deck = [&deck[n..], &deck[..n]].concat();
-            }
-            Instruction::Interleave(skip) => {
-                let mut table = vec![0; deck.len()];
-                for (i, x) in deck.iter().enumerate() {
-                    table[(i * skip) % deck.len()] = *x;
-                }
-                deck = table;
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Reverse => write!(f, "deal into new stack"),
+            Instruction::Interleave(skip) => write!(f, "deal with increment {}", skip),
+            Instruction::Cut(amount) => write!(f, "cut {}", amount),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShuffleError {
+    /// Something that isn't an instruction, along with its (1-based) line
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// Dealing with an increment that shares a factor with the deck size puts several
+    /// cards in the same place, so it isn't a shuffle at all
+    NotInvertible {
+        increment: usize,
+        deck_size: u64,
+    },
+    EmptyDeck,
+    /// A card or position that isn't in the deck
+    OutOfRange {
+        index: u64,
+        deck_size: u64,
+    },
+}
+
+impl fmt::Display for ShuffleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShuffleError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ShuffleError::NotInvertible {
+                increment,
+                deck_size,
+            } => write!(
+                f,
+                "can't deal {} cards with increment {}, they'd land on top of each other",
+                deck_size, increment
+            ),
+            ShuffleError::EmptyDeck => write!(f, "there are no cards to shuffle"),
+            ShuffleError::OutOfRange { index, deck_size } => {
+                write!(f, "there's no {} in a deck of {} cards", index, deck_size)
             }
         }
     }
+}
+
+impl Error for ShuffleError {}
 
-    deck.iter().position(|x| *x == 2019).unwrap()
+/// A list of shuffle instructions, which works on a deck of any size without dealing it
+/// out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shuffle {
+    instructions: Vec<Instruction>,
 }
 
-#[aoc(day22, part2)]
-fn solve_p2(shuffle: &[Instruction]) -> u64 {
-    let mut idx = 2020;
-    let deck_length = 119315717514047u64;
-    //let deck_length = 10007;
-
-    //for _ in 0..101741582076661u64 {
-    for _ in 0..100u64 {
-        for ins in shuffle.iter().rev() {
-            match ins {
-                Instruction::Reverse => {
-                    idx = deck_length - idx - 1;
-                }
-                Instruction::Cut(amount) => {
-                    let n = if *amount > 0 {
-                        *amount as u64
-                    } else {
-                        (deck_length as isize - amount.abs()) as u64
-                    };
-
-                    if idx <= (deck_length - n) {
-                        idx += n;
-                    } else {
-                        idx = idx - (deck_length - n);
+impl Shuffle {
+    pub fn new(instructions: Vec<Instruction>) -> Shuffle {
+        Shuffle { instructions }
+    }
+
+    /// One instruction per line, ignoring blank ones
+    pub fn parse(input: &str) -> Result<Shuffle, ShuffleError> {
+        let instructions = input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                line.parse().map_err(|message| ShuffleError::Parse {
+                    line: idx + 1,
+                    message,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Shuffle { instructions })
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The whole shuffle as a single function of where a card starts
+    fn affine(&self, deck_size: u64) -> Result<Affine, ShuffleError> {
+        if deck_size == 0 {
+            return Err(ShuffleError::EmptyDeck);
+        }
+        let n = deck_size as u128;
+
+        let mut f = Affine::identity(n);
+        for ins in &self.instructions {
+            let step = match *ins {
+                Instruction::Reverse => Affine::new(-1, -1, n),
+                Instruction::Cut(amount) => Affine::new(1, -(amount as i128), n),
+                Instruction::Interleave(increment) => {
+                    if mod_inv(increment as u128 % n, n).is_none() && deck_size > 1 {
+                        return Err(ShuffleError::NotInvertible {
+                            increment,
+                            deck_size,
+                        });
                     }
+                    Affine::new((increment as u128 % n) as i128, 0, n)
                 }
-                Instruction::Interleave(skip) => {
-                    // curr_idx = prev_idx * skip (mod deck_length)
-                    idx = (idx * mod_inv(*skip as u64, deck_length)) % deck_length;
-                }
-            }
+            };
+            f = f.then(step);
         }
-        println!("{:?}", idx);
+        Ok(f)
     }
 
-    // so this gives us a sequence of numbers
-    // we now need to reverse the LCG
+    /// Where `card` ends up after shuffling `times` times
+    pub fn position_of(&self, card: u64, deck_size: u64, times: u64) -> Result<u64, ShuffleError> {
+        let f = self.affine(deck_size)?.pow(times);
+        Ok(f.apply(in_deck(card, deck_size)?) as u64)
+    }
 
-    idx
+    /// Which card is at `position` after shuffling `times` times
+    pub fn card_at(&self, position: u64, deck_size: u64, times: u64) -> Result<u64, ShuffleError> {
+        let f = self.affine(deck_size)?.pow(times);
+        // every increment was checked, so this always exists
+        let undo = f.inverse().unwrap();
+        Ok(undo.apply(in_deck(position, deck_size)?) as u64)
+    }
+
+    /// The shortest list of instructions that shuffles a deck of this size the same way.
+    /// It's never more than two: a deal or a reversal, then a cut.
+    pub fn simplify(&self, deck_size: u64) -> Result<Shuffle, ShuffleError> {
+        let f = self.affine(deck_size)?;
+        let n = f.n;
+        if n == 1 {
+            // nothing can move the only card
+            return Ok(Shuffle::new(Vec::new()));
+        }
+
+        // a reversal is -x - 1, so it leaves one more to cut than an increment would
+        let (first, b) = match f.a {
+            1 => (None, f.b),
+            a if a == n - 1 => (Some(Instruction::Reverse), f.b + 1),
+            a => (Some(Instruction::Interleave(a as usize)), f.b),
+        };
+        let cut = (n - b % n) % n;
+
+        let mut instructions: Vec<_> = first.into_iter().collect();
+        if cut > isize::MAX as u128 {
+            // too big to write down, but cutting from the bottom does the same thing
+            instructions.push(Instruction::Cut(-((n - cut) as isize)));
+        } else if cut != 0 {
+            instructions.push(Instruction::Cut(cut as isize));
+        }
+        Ok(Shuffle { instructions })
+    }
+}
+
+fn in_deck(index: u64, deck_size: u64) -> Result<u128, ShuffleError> {
+    if index < deck_size {
+        Ok(index as u128)
+    } else {
+        Err(ShuffleError::OutOfRange { index, deck_size })
+    }
+}
+
+#[aoc_generator(day22)]
+fn parse_shuffle(input: &str) -> Shuffle {
+    Shuffle::parse(input).unwrap()
+}
+
+#[aoc(day22, part1)]
+fn solve_p1(shuffle: &Shuffle) -> u64 {
+    shuffle.position_of(2019, 10007, 1).unwrap()
 }
 
-/// Precondition: a is coprie to m
-fn mod_inv(a: u64, m: u64) -> u64 {
-    power(a, m - 2, m)
+#[aoc(day22, part2)]
+fn solve_p2(shuffle: &Shuffle) -> u64 {
+    let deck_length = 119_315_717_514_047;
+    let repeats = 101_741_582_076_661;
+    shuffle.card_at(2020, deck_length, repeats).unwrap()
 }
 
-// To compute x^y under modulo m
-fn power(x: u64, y: u64, m: u64) -> u64 {
-    if y == 0 {
-        return 1;
+/// A linear function `a * x + b mod n`. Every step of the shuffle moves the card at `x` to
+/// the position it gives, and so does any number of them one after another. `n` fits in a
+/// `u64`, so multiplying two numbers below it can't overflow a `u128`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Affine {
+    a: u128,
+    b: u128,
+    n: u128,
+}
+
+impl Affine {
+    /// `a` and `b` can be anything that fits in a `u64`, including negative
+    fn new(a: i128, b: i128, n: u128) -> Affine {
+        let m = n as i128;
+        Affine {
+            a: a.rem_euclid(m) as u128,
+            b: b.rem_euclid(m) as u128,
+            n,
+        }
     }
 
-    let mut p = power(x, y / 2, m) % m;
-    p = (p * p) % m;
+    fn identity(n: u128) -> Affine {
+        Affine::new(1, 0, n)
+    }
+
+    /// This, followed by `next`
+    fn then(self, next: Affine) -> Affine {
+        let n = self.n;
+        Affine {
+            a: next.a * self.a % n,
+            b: (next.a * self.b % n + next.b) % n,
+            n,
+        }
+    }
+
+    fn apply(self, x: u128) -> u128 {
+        (self.a * (x % self.n) % self.n + self.b) % self.n
+    }
+
+    /// This, `k` times over
+    fn pow(self, mut k: u64) -> Affine {
+        let mut result = Affine::identity(self.n);
+        let mut square = self;
+        while k > 0 {
+            if k % 2 == 1 {
+                result = result.then(square);
+            }
+            square = square.then(square);
+            k /= 2;
+        }
+        result
+    }
 
-    if y % 2 == 0 {
-        p
+    /// Where a card came from, given where it ended up. `None` if `a` shares a factor with
+    /// the deck size, since then several positions end up in the same place.
+    fn inverse(self) -> Option<Affine> {
+        let n = self.n;
+        let a = mod_inv(self.a, n)?;
+        Some(Affine {
+            a,
+            b: (n - a * self.b % n) % n,
+            n,
+        })
+    }
+}
+
+/// Extended Euclid. `None` if `a` and `m` aren't coprime. Both are below 2^64, so nothing
+/// here gets near the limits of an `i128`.
+fn mod_inv(a: u128, m: u128) -> Option<u128> {
+    let m = m as i128;
+    let (mut old_r, mut r) = ((a as i128).rem_euclid(m), m);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let q = old_r / r;
+        old_r -= q * r;
+        std::mem::swap(&mut old_r, &mut r);
+        old_s -= q * s;
+        std::mem::swap(&mut old_s, &mut s);
+    }
+
+    if old_r == 1 {
+        Some(old_s.rem_euclid(m) as u128)
     } else {
-        (x * p) % m
+        None
     }
 }
+
 // Example
 //
 //
@@ -124,3 +303,148 @@ fn power(x: u64, y: u64, m: u64) -> u64 {
 // deal into new stack
 // deal into new stack
 // Result: 0 3 6 9 2 5 8 1 4 7
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The deck after shuffling, worked out backwards from each position
+    fn deal(shuffle: &str, n: u64) -> Vec<u64> {
+        let shuffle = Shuffle::parse(shuffle).unwrap();
+        (0..n)
+            .map(|pos| shuffle.card_at(pos, n, 1).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn examples() {
+        let shuffle = "deal with increment 7\ndeal into new stack\ndeal into new stack";
+        assert_eq!(deal(shuffle, 10), vec![0, 3, 6, 9, 2, 5, 8, 1, 4, 7]);
+
+        let shuffle = "cut 6\ndeal with increment 7\ndeal into new stack";
+        assert_eq!(deal(shuffle, 10), vec![3, 0, 7, 4, 1, 8, 5, 2, 9, 6]);
+
+        let shuffle = "deal with increment 7\ndeal with increment 9\ncut -2";
+        assert_eq!(deal(shuffle, 10), vec![6, 3, 0, 7, 4, 1, 8, 5, 2, 9]);
+
+        let shuffle = "deal into new stack\ncut -2\ndeal with increment 7\ncut 8\ncut -4\n\
+                       deal with increment 7\ncut 3\ndeal with increment 9\n\
+                       deal with increment 3\ncut -1";
+        assert_eq!(deal(shuffle, 10), vec![9, 2, 5, 8, 1, 4, 7, 0, 3, 6]);
+    }
+
+    #[test]
+    fn repeats() {
+        let shuffle = Shuffle::parse("deal with increment 7\ncut -3\ndeal into new stack").unwrap();
+        let mut card = 2019;
+        for _ in 0..1000 {
+            card = shuffle.position_of(card, 10007, 1).unwrap();
+        }
+        assert_eq!(shuffle.position_of(2019, 10007, 1000), Ok(card));
+        assert_eq!(shuffle.card_at(card, 10007, 1000), Ok(2019));
+
+        // near the real deck size, where 64 bits would overflow
+        let (n, times) = (119_315_717_514_047, 101_741_582_076_661);
+        let end = shuffle.position_of(2020, n, times).unwrap();
+        assert_eq!(shuffle.card_at(end, n, times), Ok(2020));
+    }
+
+    #[test]
+    fn simplify() {
+        let long = "deal into new stack\ncut -2\ndeal with increment 7\ncut 8\ncut -4\n\
+                    deal with increment 7\ncut 3\ndeal with increment 9\n\
+                    deal with increment 3\ncut -1";
+        for &n in &[10, 11, 10007] {
+            let shuffle = Shuffle::parse(long).unwrap();
+            let simple = shuffle.simplify(n).unwrap();
+            assert!(simple.instructions().len() <= 2);
+            for card in 0..10 {
+                assert_eq!(
+                    simple.position_of(card, n, 1),
+                    shuffle.position_of(card, n, 1)
+                );
+            }
+        }
+
+        let reverse = Shuffle::parse("deal into new stack\ncut 3\ncut -3").unwrap();
+        assert_eq!(
+            reverse.simplify(10).unwrap().instructions(),
+            &[Instruction::Reverse]
+        );
+        let nothing = Shuffle::parse("deal into new stack\ndeal into new stack").unwrap();
+        assert_eq!(nothing.simplify(10).unwrap().instructions(), &[]);
+
+        let one_card = Shuffle::parse("deal into new stack\ndeal with increment 3\ncut 2").unwrap();
+        assert_eq!(one_card.simplify(1).unwrap().instructions(), &[]);
+        assert_eq!(reverse.simplify(1).unwrap().instructions(), &[]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Shuffle::parse("cut 3\nshuffle vigorously"),
+            Err(ShuffleError::Parse {
+                line: 2,
+                message: "unknown instruction \"shuffle vigorously\"".to_owned()
+            })
+        );
+
+        let shuffle = Shuffle::parse("deal with increment 4").unwrap();
+        assert_eq!(
+            shuffle.card_at(0, 10, 1),
+            Err(ShuffleError::NotInvertible {
+                increment: 4,
+                deck_size: 10
+            })
+        );
+        assert_eq!(shuffle.position_of(0, 0, 1), Err(ShuffleError::EmptyDeck));
+        assert_eq!(
+            shuffle.card_at(11, 11, 1),
+            Err(ShuffleError::OutOfRange {
+                index: 11,
+                deck_size: 11
+            })
+        );
+        assert_eq!(shuffle.position_of(3, 11, 1), Ok(1));
+    }
+
+    #[test]
+    fn biggest_deck() {
+        let n = u64::MAX;
+        let twice = Shuffle::parse("deal into new stack\ndeal into new stack").unwrap();
+        assert_eq!(twice.position_of(0, n, 1), Ok(0));
+        assert_eq!(twice.position_of(n - 1, n, 12345), Ok(n - 1));
+
+        let shuffle = Shuffle::parse("deal with increment 2\ncut -7\ndeal into new stack").unwrap();
+        // 2 * (n - 1) + 7 wraps round to 5, which the reversal puts at n - 6
+        assert_eq!(shuffle.position_of(n - 1, n, 1), Ok(n - 6));
+        let times = 101_741_582_076_661;
+        let end = shuffle.position_of(n - 2, n, times).unwrap();
+        assert_eq!(shuffle.card_at(end, n, times), Ok(n - 2));
+
+        let simple = shuffle.simplify(n).unwrap();
+        for &card in &[0, 1, n / 2, n - 1] {
+            assert_eq!(
+                simple.position_of(card, n, 1),
+                shuffle.position_of(card, n, 1)
+            );
+        }
+
+        // cutting n - 5 from the top doesn't fit in an isize, so it comes out as -5
+        let cut = Shuffle::parse("cut -5").unwrap();
+        assert_eq!(
+            cut.simplify(n).unwrap().instructions(),
+            &[Instruction::Cut(-5)]
+        );
+
+        // u64::MAX is divisible by 3
+        let three = Shuffle::parse("deal with increment 3").unwrap();
+        assert_eq!(
+            three.card_at(0, n, 1),
+            Err(ShuffleError::NotInvertible {
+                increment: 3,
+                deck_size: n
+            })
+        );
+    }
+}
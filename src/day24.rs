@@ -1,156 +1,69 @@
-use std::ops::Index;
-use std::fmt;
-use std::iter::FromIterator;
+use crate::grid::{Grid, Tile as GridTile, ORTHOGONAL};
 use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::ops::Index;
 
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 enum Tile {
     Bug,
-    Empty
-}
-
-#[derive(PartialEq, Eq, Hash, Clone)]
-struct ErisMap {
-    map: Vec<Tile>,
-    width: usize,
-    height: usize,
+    Empty,
 }
 
-
-struct ErisMapIter<'a> {
-    map: &'a ErisMap,
-    index: usize,
-}
-
-impl<'a> Iterator for ErisMapIter<'a> {
-    type Item = (usize, usize, &'a Tile);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let x = self.index % self.map.width;
-        let y = self.index / self.map.width;
-        let result = Some((x, y, self.map.map.get(self.index)?));
-        self.index += 1;
-        result
-    }
-}
-
-
-impl ErisMap {
-    fn count_neighbors(&self, pos: (usize, usize)) -> usize {
-        use crate::day17::DIRECTIONS;
-
-        let mut count = 0;
-        for dir in &DIRECTIONS {
-            let x = pos.0 as i64 + dir.dx();
-            let y = pos.1 as i64 + dir.dy();
-
-            if x >= 0 && x < self.width as i64 && y >= 0 && y < self.height as i64 {
-                match self[(x as usize, y as usize)] {
-                    Tile::Bug => count += 1,
-                    Tile::Empty => { },
-                }
-            }
+impl GridTile for Tile {
+    fn from_char(c: char) -> Option<Tile> {
+        match c {
+            '#' => Some(Tile::Bug),
+            '.' => Some(Tile::Empty),
+            _ => None,
         }
-
-        count
     }
 
-    fn iter(&self) -> ErisMapIter {
-        ErisMapIter {
-            map: self,
-            index: 0,
+    fn to_char(&self) -> char {
+        match self {
+            Tile::Bug => '#',
+            Tile::Empty => '.',
         }
     }
-
-    fn step(&self) -> ErisMap {
-        // NOTE: I could make this an iterator, but 2 iterators might be confusing
-        self.iter().map(|(x, y, tile)| {
-            match tile {
-                Tile::Bug => {
-                    if self.count_neighbors((x, y)) == 1 {
-                        Tile::Bug
-                    } else {
-                        Tile::Empty
-                    }
-                },
-                Tile::Empty => {
-                    let neighbors = self.count_neighbors((x, y));
-                    if neighbors == 1 || neighbors == 2 {
-                        Tile::Bug
-                    } else {
-                        Tile::Empty
-                    }
-                }
-            }
-        }).collect()
-    }
 }
 
-impl Index<(usize, usize)> for ErisMap {
-    type Output = Tile;
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct ErisMap {
+    grid: Grid<Tile>,
+}
 
-    fn index(&self, pos: (usize, usize)) -> &Self::Output {
-        &self.map[pos.1 * self.width + pos.0]
+impl ErisMap {
+    fn count_neighbors(&self, pos: (usize, usize)) -> usize {
+        self.grid
+            .neighbours4(pos)
+            .filter(|&pos| self.grid[pos] == Tile::Bug)
+            .count()
     }
-}
 
-/// Creates an ErisMap from an ErisMapIter
-/// ASSUMES `width` and `height` are 5
-impl FromIterator<Tile> for ErisMap {
-    fn from_iter<I: IntoIterator<Item=Tile>>(iter: I) -> Self {
+    fn step(&self) -> ErisMap {
+        let grid = &self.grid;
         ErisMap {
-            map: iter.into_iter().collect(),
-            width: 5,  // Actually handle this correctly
-            height: 5,
+            grid: Grid::from_fn(grid.width(), grid.height(), |pos| {
+                match (grid[pos], self.count_neighbors(pos)) {
+                    (Tile::Bug, 1) => Tile::Bug,
+                    (Tile::Empty, 1) | (Tile::Empty, 2) => Tile::Bug,
+                    _ => Tile::Empty,
+                }
+            }),
         }
     }
 }
 
 impl fmt::Debug for ErisMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                write!(
-                    f,
-                    "{}",
-                    match self[(x, y)] {
-                        Tile::Bug => '#',
-                        Tile::Empty => ' ',
-                    }
-                )?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        write!(f, "{}", self.grid)
     }
 }
 
-
-
 #[aoc_generator(day24)]
 fn parse_map(input: &str) -> ErisMap {
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
-
-    let mut map = Vec::new();
-
-    for line in input.lines() {
-        for c in line.chars() {
-            let tile_type = match c {
-                '#' => Tile::Bug,
-                '.' => Tile::Empty,
-                c => panic!("Unrecognized tile: {:?}", c),
-            };
-            map.push(tile_type);
-        }
-    }
-
     ErisMap {
-        map: map,
-        width: width,
-        height: height,
+        grid: Grid::parse(input).unwrap(),
     }
-
 }
 
 #[aoc(day24, part1)]
@@ -161,36 +74,43 @@ fn solve_p1(map: &ErisMap) -> usize {
     loop {
         let new_map = map.step();
         if past.contains(&new_map) {
-            return new_map.iter().map(|(x, y, tile)| {
-                let i = y * map.width + x;
-                match tile {
-                    Tile::Empty => 0,
-                    Tile::Bug => 2usize.pow(i as u32),
-                }
-            }).sum();
+            return new_map
+                .grid
+                .iter()
+                .map(|((x, y), tile)| {
+                    let i = y * map.grid.width() + x;
+                    match tile {
+                        Tile::Empty => 0,
+                        Tile::Bug => 2usize.pow(i as u32),
+                    }
+                })
+                .sum();
         }
         map = new_map;
         past.insert(map.clone());
     }
 }
 
+/// Levels of the recursive grid, from the outermost one we've needed so far inwards. Each
+/// level's centre tile holds the next level in.
 #[derive(PartialEq, Eq, Hash, Clone)]
 struct RecursiveErisMap {
-    map: VecDeque<Vec<Tile>>,
+    map: VecDeque<Grid<Tile>>,
     width: usize,
     height: usize,
 }
 
 impl fmt::Debug for RecursiveErisMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for depth in self.map.len() {
-            println!("Depth {}", depth);
+        for depth in 0..self.map.len() {
+            writeln!(f, "Depth {}", depth)?;
             for y in 0..self.height {
                 for x in 0..self.width {
                     write!(
                         f,
                         "{}",
                         match self[(depth, x, y)] {
+                            _ if (x, y) == self.centre() => '?',
                             Tile::Bug => '#',
                             Tile::Empty => ' ',
                         }
@@ -203,48 +123,121 @@ impl fmt::Debug for RecursiveErisMap {
     }
 }
 
-
 /// Indexes the RecursiveErisMap using tuple (depth, x, y)
 impl Index<(usize, usize, usize)> for RecursiveErisMap {
     type Output = Tile;
 
     fn index(&self, pos: (usize, usize, usize)) -> &Self::Output {
-        &self.map[pos.0][pos.2 * self.width + pos.1]
+        &self.map[pos.0][(pos.1, pos.2)]
     }
 }
 
-
+impl From<&ErisMap> for RecursiveErisMap {
+    fn from(map: &ErisMap) -> Self {
+        RecursiveErisMap {
+            map: VecDeque::from(vec![map.grid.clone()]),
+            width: map.grid.width(),
+            height: map.grid.height(),
+        }
+    }
+}
 
 impl RecursiveErisMap {
-    fn count_neighbors(&self, pos: (usize, usize, usize)) -> usize {
-        use crate::day17::DIRECTIONS;
+    fn centre(&self) -> (usize, usize) {
+        (self.width / 2, self.height / 2)
+    }
 
-        let mut count = 0;
-        for dir in &DIRECTIONS {
-            let x = pos.1 as i64 + dir.dx();
-            let y = pos.2 as i64 + dir.dy();
+    /// Like indexing, except levels we haven't got to yet are empty
+    fn is_bug(&self, depth: i64, x: usize, y: usize) -> bool {
+        depth >= 0 && (depth as usize) < self.map.len() && self[(depth as usize, x, y)] == Tile::Bug
+    }
 
-            if x >= 0 && x < self.width as i64 && y >= 0 && y < self.height as i64 {
-                match self[(pos.0, x as usize, y as usize)] {
-                    Tile::Bug => count += 1,
-                    Tile::Empty => { },
+    fn count_neighbors(&self, pos: (usize, usize, usize)) -> usize {
+        let depth = pos.0 as i64;
+        let (cx, cy) = self.centre();
+        let mut count = 0;
+        for &(dx, dy) in &ORTHOGONAL {
+            let x = pos.1 as i64 + dx;
+            let y = pos.2 as i64 + dy;
+
+            if x < 0 || x >= self.width as i64 || y < 0 || y >= self.height as i64 {
+                // off the edge, so it's the tile next to the centre of the level outside
+                let outer_x = (cx as i64 + dx) as usize;
+                let outer_y = (cy as i64 + dy) as usize;
+                if self.is_bug(depth - 1, outer_x, outer_y) {
+                    count += 1;
                 }
-            } else {
-                // we need to check the (depth - 1) layer
+            } else if (x as usize, y as usize) == (cx, cy) {
+                // into the centre, so it's the whole near edge of the level inside
+                let edge: Vec<_> = match (dx, dy) {
+                    (1, _) => (0..self.height).map(|y| (0, y)).collect(),
+                    (-1, _) => (0..self.height).map(|y| (self.width - 1, y)).collect(),
+                    (_, 1) => (0..self.width).map(|x| (x, 0)).collect(),
+                    _ => (0..self.width).map(|x| (x, self.height - 1)).collect(),
+                };
+                count += edge
+                    .into_iter()
+                    .filter(|&(x, y)| self.is_bug(depth + 1, x, y))
+                    .count();
+            } else if self.is_bug(depth, x as usize, y as usize) {
+                count += 1;
             }
         }
 
         count
     }
+
+    fn step(&self) -> RecursiveErisMap {
+        // bugs can spread one level further out and one further in each minute
+        let mut grown = self.clone();
+        let empty = Grid::new(self.width, self.height, Tile::Empty);
+        let has_bugs = |level: &Grid<Tile>| level.position(|&tile| tile == Tile::Bug).is_some();
+        if grown.map.front().is_some_and(has_bugs) {
+            grown.map.push_front(empty.clone());
+        }
+        if grown.map.back().is_some_and(has_bugs) {
+            grown.map.push_back(empty);
+        }
+
+        let centre = self.centre();
+        let map = (0..grown.map.len())
+            .map(|depth| {
+                Grid::from_fn(self.width, self.height, |(x, y)| {
+                    let neighbors = grown.count_neighbors((depth, x, y));
+                    match grown[(depth, x, y)] {
+                        _ if (x, y) == centre => Tile::Empty,
+                        Tile::Bug if neighbors == 1 => Tile::Bug,
+                        Tile::Empty if neighbors == 1 || neighbors == 2 => Tile::Bug,
+                        _ => Tile::Empty,
+                    }
+                })
+            })
+            .collect();
+
+        RecursiveErisMap { map, ..grown }
+    }
+
+    fn bugs(&self) -> usize {
+        self.map
+            .iter()
+            .map(|level| level.iter().filter(|&(_, &tile)| tile == Tile::Bug).count())
+            .sum()
+    }
 }
 
+fn bugs_after(map: &ErisMap, minutes: usize) -> usize {
+    let mut map = RecursiveErisMap::from(map);
+    for _ in 0..minutes {
+        map = map.step();
+    }
+    map.bugs()
+}
 
 #[aoc(day24, part2)]
 fn solve_p2(map: &ErisMap) -> usize {
-    0
+    bugs_after(map, 200)
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -259,4 +252,15 @@ mod tests {
 #...."[1..];
         assert_eq!(2129920, solve_p1(&parse_map(input)));
     }
+
+    #[test]
+    fn day24_test2() {
+        let input = &"
+....#
+#..#.
+#..##
+..#..
+#...."[1..];
+        assert_eq!(99, bugs_after(&parse_map(input), 10));
+    }
 }
@@ -0,0 +1,516 @@
+use crate::day9::parse_program;
+use crate::intcode::ascii::{AsciiMachine, Screen};
+use crate::intcode::{Vm, VmError};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, Write};
+
+/// Items that end the game one way or another if you pick them up
+const TRAPS: [&str; 5] = [
+    "giant electromagnet",
+    "infinite loop",
+    "molten lava",
+    "photons",
+    "escape pod",
+];
+
+/// Way more than any normal command takes, but stops the infinite loop from hanging us if
+/// it turns up under a different name
+const STEP_LIMIT: u64 = 1_000_000;
+
+#[aoc_generator(day25)]
+fn day25_gen(input: &str) -> Vec<i64> {
+    parse_program(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Room {
+    name: String,
+    doors: Vec<String>,
+    items: Vec<String>,
+}
+
+/// Every room described in `text`, in order. There's usually just the one, but getting
+/// thrown out of the pressure-sensitive floor describes it and then where you landed.
+fn parse_rooms(text: &str) -> Vec<Room> {
+    let mut rooms: Vec<Room> = Vec::new();
+    let mut list = None;
+
+    for line in text.lines() {
+        if line.starts_with("== ") && line.ends_with(" ==") {
+            rooms.push(Room {
+                name: line[3..line.len() - 3].to_owned(),
+                doors: Vec::new(),
+                items: Vec::new(),
+            });
+            list = None;
+        } else if line == "Doors here lead:" {
+            list = Some(true);
+        } else if line == "Items here:" {
+            list = Some(false);
+        } else if let (Some(entry), Some(room)) = (line.strip_prefix("- "), rooms.last_mut()) {
+            match list {
+                Some(true) => room.doors.push(entry.to_owned()),
+                Some(false) => room.items.push(entry.to_owned()),
+                None => {}
+            }
+        } else {
+            list = None;
+        }
+    }
+    rooms
+}
+
+fn opposite(door: &str) -> &'static str {
+    match door {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        "west" => "east",
+        _ => panic!("Unknown direction {:?}", door),
+    }
+}
+
+/// The number Santa tells you to type in once you're through
+fn password(text: &str) -> Option<i64> {
+    let rest = &text[text.find("typing ")? + 7..];
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+struct Droid {
+    machine: AsciiMachine,
+    /// Every room seen so far, by name
+    rooms: HashMap<String, Room>,
+    /// Where each door of each room leads
+    doors: HashMap<(String, String), String>,
+    inventory: Vec<String>,
+    /// The room in front of the pressure-sensitive floor, and which way the floor is
+    floor: Option<(String, String)>,
+}
+
+impl Droid {
+    fn new(tape: &[i64]) -> Droid {
+        Droid {
+            machine: AsciiMachine::new(Vm::new(tape)),
+            rooms: HashMap::new(),
+            doors: HashMap::new(),
+            inventory: Vec::new(),
+            floor: None,
+        }
+    }
+
+    fn command(&mut self, command: &str) -> Result<Screen, VmError> {
+        let vm = self.machine.vm_mut();
+        vm.set_step_limit(Some(vm.steps() + STEP_LIMIT));
+        self.machine.send_line(command);
+        self.machine.read_until_prompt()
+    }
+
+    /// Picks up an item unless it's a trap. Anything that makes the game end or hang counts
+    /// as a trap too, and gets undone.
+    fn take(&mut self, item: &str) -> Result<bool, VmError> {
+        if TRAPS.contains(&item) {
+            return Ok(false);
+        }
+
+        let saved = self.machine.vm().snapshot();
+        match self.command(&format!("take {}", item)) {
+            Ok(screen) if !screen.halted => {
+                self.inventory.push(item.to_owned());
+                Ok(true)
+            }
+            Ok(_) | Err(VmError::StepLimitExceeded { .. }) => {
+                self.machine.vm_mut().restore(&saved);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Visits every room reachable from `room`, picking up everything safe along the way,
+    /// and comes back to it
+    fn explore(&mut self, room: Room) -> Result<(), VmError> {
+        for item in &room.items {
+            self.take(item)?;
+        }
+        self.rooms.insert(room.name.clone(), room.clone());
+
+        for door in &room.doors {
+            let key = (room.name.clone(), door.clone());
+            if self.doors.contains_key(&key) {
+                continue;
+            }
+
+            let mut arrived = parse_rooms(&self.command(door)?.text);
+            let next = match arrived.len() {
+                0 => panic!("Went {} from {} and ended up nowhere", door, room.name),
+                1 => arrived.remove(0),
+                _ => {
+                    // thrown back out, so we haven't gone anywhere
+                    self.doors.insert(key, arrived[0].name.clone());
+                    self.floor = Some((room.name.clone(), door.clone()));
+                    continue;
+                }
+            };
+
+            self.doors.insert(key, next.name.clone());
+            self.doors.insert(
+                (next.name.clone(), opposite(door).to_owned()),
+                room.name.clone(),
+            );
+            if !self.rooms.contains_key(&next.name) {
+                self.explore(next)?;
+            }
+            self.command(opposite(door))?;
+        }
+        Ok(())
+    }
+
+    /// The doors to go through to get from one room to another
+    fn route(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut queue = VecDeque::new();
+        let mut seen = HashSet::new();
+        queue.push_back((from.to_owned(), Vec::new()));
+        seen.insert(from.to_owned());
+
+        while let Some((room, path)) = queue.pop_front() {
+            if room == to {
+                return Some(path);
+            }
+            for door in &self.rooms[&room].doors {
+                if let Some(next) = self.doors.get(&(room.clone(), door.clone())) {
+                    if self.rooms.contains_key(next) && seen.insert(next.clone()) {
+                        let mut path = path.clone();
+                        path.push(door.clone());
+                        queue.push_back((next.clone(), path));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Tries every combination of items on the floor, changing one item at a time, until
+    /// one weighs the right amount
+    fn crack_floor(&mut self, door: &str) -> Result<Option<i64>, VmError> {
+        let items = self.inventory.clone();
+        for item in &items {
+            self.command(&format!("drop {}", item))?;
+        }
+
+        let mut held = vec![false; items.len()];
+        for combination in 0..1u64 << items.len() {
+            if combination > 0 {
+                // consecutive Gray codes differ in exactly this bit
+                let bit = combination.trailing_zeros() as usize;
+                held[bit] = !held[bit];
+                let verb = if held[bit] { "take" } else { "drop" };
+                self.command(&format!("{} {}", verb, items[bit]))?;
+            }
+
+            let screen = self.command(door)?;
+            if !screen.text.contains("ejected") {
+                return Ok(password(&screen.text));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[aoc(day25, part1)]
+fn solve_p1(tape: &[i64]) -> i64 {
+    let mut droid = Droid::new(tape);
+    let start = parse_rooms(&droid.machine.read_until_prompt().unwrap().text)
+        .pop()
+        .expect("Didn't start in a room");
+    let start_name = start.name.clone();
+    droid.explore(start).unwrap();
+
+    let (checkpoint, door) = droid
+        .floor
+        .clone()
+        .expect("Never found the pressure-sensitive floor");
+    let route = droid
+        .route(&start_name, &checkpoint)
+        .expect("No way back to the checkpoint");
+    for step in route {
+        droid.command(&step).unwrap();
+    }
+
+    droid
+        .crack_floor(&door)
+        .unwrap()
+        .expect("No combination of items got past the floor")
+}
+
+/// Plays the game by hand. `n`, `s`, `e` and `w` move, and `i` lists the inventory.
+pub fn play<R: BufRead, W: Write>(tape: &[i64], input: R, mut output: W) -> io::Result<()> {
+    let mut machine = AsciiMachine::new(Vm::new(tape));
+    let mut lines = input.lines();
+    loop {
+        let screen = machine.read_until_prompt().map_err(io::Error::other)?;
+        write!(output, "{}", screen.text)?;
+        if screen.halted {
+            break;
+        }
+
+        output.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let command = match line.trim() {
+            "n" => "north",
+            "s" => "south",
+            "e" => "east",
+            "w" => "west",
+            "i" => "inv",
+            command => command,
+        };
+        machine.send_line(command);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    /// What the stand-in adventure below boils each command down to
+    fn key(command: &str) -> i64 {
+        command
+            .bytes()
+            .fold(0, |h: i64, c| h.wrapping_mul(31).wrapping_add(i64::from(c)))
+    }
+
+    /// Four rooms in a loop, with the floor north of the checkpoint. It wants the mutex and
+    /// the spool but not the coin. The lava and the cursed rock end the game, and the jinx
+    /// hangs it.
+    fn adventure() -> Vec<i64> {
+        let source = format!(
+            r#"
+            add #hull, #0, [str]
+    show:   add [str], #0, [test+1]
+            add [str], #0, [emit+1]
+    test:   jz [0], shown
+    emit:   out [0]
+            add [str], #1, [str]
+            jz #0, show
+    shown:  jz [halt], read
+            hlt
+
+    read:   add #0, #0, [h]
+    char:   in [c]
+            eq [c], #10, [t]
+            jnz [t], dispatch
+            mul [h], #31, [h]
+            add [h], [c], [h]
+            jz #0, char
+    dispatch: eq [h], #{north}, [t]
+            jnz [t], north
+            eq [h], #{south}, [t]
+            jnz [t], south
+            eq [h], #{east}, [t]
+            jnz [t], east
+            eq [h], #{west}, [t]
+            jnz [t], west
+            eq [h], #{take_mutex}, [t]
+            jnz [t], tmutex
+            eq [h], #{take_coin}, [t]
+            jnz [t], tcoin
+            eq [h], #{take_spool}, [t]
+            jnz [t], tspool
+            eq [h], #{drop_mutex}, [t]
+            jnz [t], dmutex
+            eq [h], #{drop_coin}, [t]
+            jnz [t], dcoin
+            eq [h], #{drop_spool}, [t]
+            jnz [t], dspool
+            eq [h], #{take_lava}, [t]
+            jnz [t], tlava
+            eq [h], #{take_rock}, [t]
+            jnz [t], trock
+            eq [h], #{take_jinx}, [t]
+            jnz [t], tjinx
+            add #huh, #0, [str]
+            jz #0, show
+
+    north:  add #0, #0, [dir]
+            jz #0, move
+    south:  add #1, #0, [dir]
+            jz #0, move
+    east:   add #2, #0, [dir]
+            jz #0, move
+    west:   add #3, #0, [dir]
+    move:   mul [room], #4, [t]
+            add [t], [dir], [t]
+            add [t], #doors, [door+1]
+    door:   add [0], #0, [next]
+            eq [next], #-1, [t]
+            jnz [t], nowhere
+            eq [next], #4, [t]
+            jnz [t], floor
+            add [next], #0, [room]
+            add [next], #texts, [text+1]
+    text:   add [0], #0, [str]
+            jz #0, show
+    nowhere: add #cant, #0, [str]
+            jz #0, show
+    floor:  mul [held1], #2, [t]
+            add [held0], [t], [w]
+            mul [held2], #4, [t]
+            add [w], [t], [w]
+            eq [w], #5, [t]
+            jnz [t], through
+            add #ejected, #0, [str]
+            jz #0, show
+    through: add #won, #0, [str]
+            add #1, #0, [halt]
+            jz #0, show
+
+    tmutex: add #1, #0, [held0]
+            jz #0, ok
+    tcoin:  add #1, #0, [held1]
+            jz #0, ok
+    tspool: add #1, #0, [held2]
+            jz #0, ok
+    dmutex: add #0, #0, [held0]
+            jz #0, ok
+    dcoin:  add #0, #0, [held1]
+            jz #0, ok
+    dspool: add #0, #0, [held2]
+    ok:     add #okay, #0, [str]
+            jz #0, show
+    tlava:  add #burnt, #0, [str]
+            add #1, #0, [halt]
+            jz #0, show
+    trock:  add #crushed, #0, [str]
+            add #1, #0, [halt]
+            jz #0, show
+    tjinx:  jz #0, tjinx
+
+    str:    data 0
+    h:      data 0
+    c:      data 0
+    t:      data 0
+    dir:    data 0
+    next:   data 0
+    w:      data 0
+    room:   data 0
+    halt:   data 0
+    held0:  data 0
+    held1:  data 0
+    held2:  data 0
+    ; north, south, east and west out of each room, where 4 is the floor
+    doors:  data 1, -1, 2, -1, -1, 0, 3, -1, 3, -1, -1, 0, 4, 2, -1, 1
+    texts:  data hull, galley, lab, checkpoint
+
+    hull:   data "\n\n\n== Hull ==\nA hole lets the cold in.\n\nDoors here lead:\n- north\n- east\n\nItems here:\n- mutex\n\nCommand?\n", 0
+    galley: data "\n\n\n== Galley ==\nIt smells of burnt toast.\n\nDoors here lead:\n- south\n- east\n\nItems here:\n- coin\n- molten lava\n- jinx\n\nCommand?\n", 0
+    lab:    data "\n\n\n== Lab ==\nSomething bubbles.\n\nDoors here lead:\n- north\n- west\n\nItems here:\n- spool\n- cursed rock\n\nCommand?\n", 0
+    ejected: data "\n\n\n== Pressure-Sensitive Floor ==\nAnalyzing...\n\nDoors here lead:\n- south\n\nA loud robotic voice says you're the wrong weight and you are ejected back to the checkpoint.\n"
+    checkpoint: data "\n\n\n== Security Checkpoint ==\nThe floor is just north.\n\nDoors here lead:\n- north\n- south\n- west\n\nCommand?\n", 0
+    won:    data "\n\n\n== Pressure-Sensitive Floor ==\nAnalysis complete! Get in by typing 1234 on the keypad.\n", 0
+    cant:   data "\nYou can't go that way.\n\nCommand?\n", 0
+    huh:    data "\nUnrecognized command.\n\nCommand?\n", 0
+    okay:   data "\nDone.\n\nCommand?\n", 0
+    burnt:  data "\nThe molten lava is way too hot! You melt!\n", 0
+    crushed: data "\nThe cursed rock crushes you.\n", 0
+            "#,
+            north = key("north"),
+            south = key("south"),
+            east = key("east"),
+            west = key("west"),
+            take_mutex = key("take mutex"),
+            take_coin = key("take coin"),
+            take_spool = key("take spool"),
+            drop_mutex = key("drop mutex"),
+            drop_coin = key("drop coin"),
+            drop_spool = key("drop spool"),
+            take_lava = key("take molten lava"),
+            take_rock = key("take cursed rock"),
+            take_jinx = key("take jinx"),
+        );
+        assemble(&source).unwrap()
+    }
+
+    #[test]
+    fn standin_adventure() {
+        let tape = adventure();
+        let mut droid = Droid::new(&tape);
+        let start = parse_rooms(&droid.machine.read_until_prompt().unwrap().text)
+            .pop()
+            .unwrap();
+        droid.explore(start).unwrap();
+        assert_eq!(droid.rooms.len(), 4);
+        assert_eq!(droid.inventory, vec!["mutex", "coin", "spool"]);
+        assert_eq!(
+            droid.floor,
+            Some(("Security Checkpoint".to_owned(), "north".to_owned()))
+        );
+
+        assert_eq!(solve_p1(&tape), 1234);
+    }
+
+    #[test]
+    fn rooms() {
+        let text = "
+
+== Hull Breach ==
+You got in through a hole in the floor here.
+
+Doors here lead:
+- north
+- west
+
+Items here:
+- mouse
+- food ration
+
+Command?
+";
+        assert_eq!(
+            parse_rooms(text),
+            vec![Room {
+                name: "Hull Breach".to_owned(),
+                doors: vec!["north".to_owned(), "west".to_owned()],
+                items: vec!["mouse".to_owned(), "food ration".to_owned()],
+            }]
+        );
+    }
+
+    #[test]
+    fn pressure_floor() {
+        let ejected = "
+
+== Pressure-Sensitive Floor ==
+Analyzing...
+
+Doors here lead:
+- east
+
+A loud, robotic voice says \"Alert! Droids on this ship are lighter than the detected value!\" and you are ejected back to the checkpoint.
+
+
+
+== Security Checkpoint ==
+In the next room, a pressure-sensitive floor will verify your identity.
+
+Doors here lead:
+- north
+- west
+
+Command?
+";
+        let rooms = parse_rooms(ejected);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[1].name, "Security Checkpoint");
+        assert_eq!(rooms[1].doors, vec!["north", "west"]);
+        assert_eq!(password(ejected), None);
+
+        let through = "A loud, robotic voice says \"Analysis complete! You may proceed.\" and you \
+                       enter the cockpit.\n\"Oh, hello! You should be able to get in by typing \
+                       2147485856 on the keypad at the main airlock.\"\n";
+        assert_eq!(password(through), Some(2147485856));
+    }
+}
@@ -32,7 +32,7 @@ fn parse_wires(input: &str) -> [Vec<Step>; 2] {
         let mut path = Vec::new();
 
         for step in path_text {
-            let step = match step.chars().nth(0).unwrap() {
+            let step = match step.chars().next().unwrap() {
                 'U' => Step::Up(step[1..].parse().unwrap()),
                 'D' => Step::Down(step[1..].parse().unwrap()),
                 'L' => Step::Left(step[1..].parse().unwrap()),
@@ -56,7 +56,7 @@ fn solve_p1(wires: &[Vec<Step>; 2]) -> i32 {
     let intersections = wire1_points.intersection(&wire2_points);
 
     let int = intersections
-        .min_by_key(|a| (a[0].abs() + a[1].abs()))
+        .min_by_key(|a| a[0].abs() + a[1].abs())
         .unwrap();
 
     int[0].abs() + int[1].abs()
@@ -99,7 +99,7 @@ fn solve_p2(wires: &[Vec<Step>; 2]) -> usize {
 fn points_from_steps(wire: &[Step]) -> (HashSet<[i32; 2]>, Vec<[i32; 2]>) {
     use std::iter::FromIterator;
     let path = path_from_steps(wire);
-    (HashSet::from_iter(path.clone().into_iter()), path)
+    (HashSet::from_iter(path.clone()), path)
 }
 
 fn path_from_steps(wire: &[Step]) -> Vec<[i32; 2]> {
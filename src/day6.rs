@@ -51,8 +51,7 @@ fn solve_p1(orbits: &(Vec<(usize, usize)>, usize)) -> usize {
     let mut stack = Vec::new();
     stack.push((root, 0));
     let mut total_count = 0;
-    while !stack.is_empty() {
-        let (node, depth) = stack.pop().unwrap();
+    while let Some((node, depth)) = stack.pop() {
         for (orbitee, orbiter) in orbits.iter() {
             if *orbitee == node {
                 // This is a child of our current node
@@ -1,432 +1,84 @@
 use crate::day5::parse_program;
+use crate::intcode::{Topology, Vm};
 use itertools::Itertools;
+use std::ops::RangeInclusive;
+use std::thread;
 
 #[aoc_generator(day7, part1)]
-fn p1_generator(input: &str) -> Vec<i32> {
+fn p1_generator(input: &str) -> Vec<i64> {
     parse_program(input)
 }
 
-#[aoc(day7, part1)]
-fn solve_p1(tape: &[i32]) -> i32 {
-    let sequenecs = (0..=4).permutations(5);
-
-    let mut max_signal = 0;
-    for seq in sequenecs {
-        let mut output = 0;
-        for phase in seq {
-            let mut phase_passed = false;
-            output = intcode_computer(tape, || {
-                if phase_passed {
-                    output
-                } else {
-                    phase_passed = true;
-                    phase
-                }
-            });
-        }
-        if output > max_signal {
-            max_signal = output;
-        }
+/// Runs a row of amplifiers, one per phase setting, starting from a signal of 0. With
+/// `feedback`, the last one feeds back into the first until they all halt.
+fn amplify(tape: &[i64], phases: &[i64], feedback: bool) -> i64 {
+    let mut amps = Topology::new();
+    let ids: Vec<_> = phases
+        .iter()
+        .map(|&phase| amps.add(Vm::new(tape), vec![phase]))
+        .collect();
+    if feedback {
+        amps.ring(&ids);
+    } else {
+        amps.chain(&ids);
     }
 
-    max_signal
+    amps.input(ids[0], 0);
+    amps.run().unwrap();
+    *amps
+        .last_output(ids[ids.len() - 1])
+        .expect("Amplifier didn't produce a signal")
 }
 
-fn intcode_computer<F>(tape: &[i32], mut get_input: F) -> i32
-where
-    F: FnMut() -> i32,
-{
-    use crate::day4::get_digits;
-    use std::convert::TryInto;
-    let mut tape = tape.to_owned();
-    let mut i = 0;
-    loop {
-        let instr: u64 = tape[i].try_into().expect("Negative Instruction is Invalid");
-        let mut digits = get_digits(instr);
-
-        while digits.len() < 4 {
-            digits.insert(0, 0);
-        }
-
-        let opcode = if digits.len() == 2 {
-            [digits[0], digits[1]]
-        } else if digits.len() == 1 {
-            [0, digits[0]]
-        } else {
-            [digits[digits.len() - 2], digits[digits.len() - 1]]
-        };
-
-        //println!("{:?}", opcode);
-
-        match opcode {
-            [0, 1] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[i + 1] as usize],
-                    1 => tape[i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[i + 2] as usize],
-                    1 => tape[i + 2],
-                    _ => unreachable!(),
-                };
-
-                let output = tape[i + 3] as usize;
-                tape[output] = p1 + p2;
-                i += 4;
-            }
-            [0, 2] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                //println!("p1: {} p2: {}", p1, p2);
-
-                let p1 = match p1 {
-                    0 => tape[tape[i + 1] as usize],
-                    1 => tape[i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[i + 2] as usize],
-                    1 => tape[i + 2],
-                    _ => unreachable!(),
-                };
-
-                //println!("p1: {} p2: {}", p1, p2);
-
-                let output = tape[i + 3] as usize;
-                tape[output] = p1 * p2;
-                i += 4;
-            }
-            [0, 3] => {
-                let output = tape[i + 1] as usize;
-                tape[output] = get_input();
-                i += 2;
-            }
-            [0, 4] => return tape[tape[i + 1] as usize],
-            [0, 5] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[i + 1] as usize],
-                    1 => tape[i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[i + 2] as usize],
-                    1 => tape[i + 2],
-                    _ => unreachable!(),
-                };
-
-                if p1 != 0 {
-                    i = p2 as usize;
-                } else {
-                    i += 3;
-                }
-            }
-            [0, 6] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[i + 1] as usize],
-                    1 => tape[i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[i + 2] as usize],
-                    1 => tape[i + 2],
-                    _ => unreachable!(),
-                };
-
-                if p1 == 0 {
-                    i = p2 as usize;
-                } else {
-                    i += 3;
-                }
-            }
-            [0, 7] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[i + 1] as usize],
-                    1 => tape[i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[i + 2] as usize],
-                    1 => tape[i + 2],
-                    _ => unreachable!(),
-                };
-
-                let output = tape[i + 3] as usize;
-
-                if p1 < p2 {
-                    tape[output] = 1;
-                } else {
-                    tape[output] = 0;
-                }
-                i += 4;
-            }
-            [0, 8] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[i + 1] as usize],
-                    1 => tape[i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[i + 2] as usize],
-                    1 => tape[i + 2],
-                    _ => unreachable!(),
-                };
-
-                let output = tape[i + 3] as usize;
+/// Tries every ordering of `phases`, split up between as many threads as there are cores
+fn best_signal(tape: &[i64], phases: RangeInclusive<i64>, feedback: bool) -> i64 {
+    let sequences: Vec<_> = phases.permutations(5).collect();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let per_thread = sequences.len().div_ceil(threads).max(1);
+
+    thread::scope(|s| {
+        let handles: Vec<_> = sequences
+            .chunks(per_thread)
+            .map(|chunk| {
+                s.spawn(move || chunk.iter().map(|seq| amplify(tape, seq, feedback)).max())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .max()
+            .unwrap_or(0)
+    })
+}
 
-                if p1 == p2 {
-                    tape[output] = 1;
-                } else {
-                    tape[output] = 0;
-                }
-                i += 4;
-            }
-            [9, 9] => break 0,
-            _ => panic!("Unrecognized opcode: {:?}", opcode),
-        }
-    }
+#[aoc(day7, part1)]
+fn solve_p1(tape: &[i64]) -> i64 {
+    best_signal(tape, 0..=4, false)
 }
 
 #[aoc_generator(day7, part2)]
-fn p2_generator(input: &str) -> Vec<i32> {
+fn p2_generator(input: &str) -> Vec<i64> {
     parse_program(input)
 }
 
 #[aoc(day7, part2)]
-fn solve_p2(tape: &[i32]) -> i32 {
-    let sequences = (5..=9).permutations(5);
-
-    let mut max_signal = 0;
-    for seq in sequences {
-        let mut output = 0;
-        let mut tapes = vec![tape.to_owned(); seq.len()];
-        let mut ips = vec![0; seq.len()];
-        let mut phases_passed = vec![false; seq.len()];
-        'feedback: loop {
-            for (i, phase) in seq.iter().enumerate() {
-                let state = intcode_computer_p2(&mut tapes[i], &mut ips[i], || {
-                    if !phases_passed[i] {
-                        phases_passed[i] = true;
-                        //println!("Phase passed for {:?}: {:?}", i, phase);
-                        *phase
-                    } else {
-                        output
-                    }
-                });
-
-                //println!("{:?}", state);
-
-                if state == -1 {
-                    break 'feedback;
-                }
-
-                output = state;
-            }
-        }
-        if output > max_signal {
-            max_signal = output;
-        }
-    }
-
-    max_signal
-}
-
-fn intcode_computer_p2<F>(tape: &mut Vec<i32>, i: &mut usize, mut get_input: F) -> i32
-where
-    F: FnMut() -> i32,
-{
-    use crate::day4::get_digits;
-    use std::convert::TryInto;
-
-    //let mut tape = tape.clone();
-    loop {
-        let instr: u64 = tape[*i]
-            .try_into()
-            .expect("Negative Instruction is Invalid");
-        let mut digits = get_digits(instr);
-
-        while digits.len() < 4 {
-            digits.insert(0, 0);
-        }
-
-        let opcode = if digits.len() == 2 {
-            [digits[0], digits[1]]
-        } else if digits.len() == 1 {
-            [0, digits[0]]
-        } else {
-            [digits[digits.len() - 2], digits[digits.len() - 1]]
-        };
-
-        //println!("{:?}", opcode);
-
-        match opcode {
-            [0, 1] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[*i + 1] as usize],
-                    1 => tape[*i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[*i + 2] as usize],
-                    1 => tape[*i + 2],
-                    _ => unreachable!(),
-                };
-
-                let output = tape[*i + 3] as usize;
-                tape[output] = p1 + p2;
-                *i += 4;
-            }
-            [0, 2] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                //println!("p1: {} p2: {}", p1, p2);
-
-                let p1 = match p1 {
-                    0 => tape[tape[*i + 1] as usize],
-                    1 => tape[*i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[*i + 2] as usize],
-                    1 => tape[*i + 2],
-                    _ => unreachable!(),
-                };
-
-                //println!("p1: {} p2: {}", p1, p2);
-
-                let output = tape[*i + 3] as usize;
-                tape[output] = p1 * p2;
-                *i += 4;
-            }
-            [0, 3] => {
-                let output = tape[*i + 1] as usize;
-                tape[output] = get_input();
-                *i += 2;
-            }
-            [0, 4] => {
-                *i += 2;
-                break tape[tape[*i - 1] as usize];
-            }
-            [0, 5] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[*i + 1] as usize],
-                    1 => tape[*i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[*i + 2] as usize],
-                    1 => tape[*i + 2],
-                    _ => unreachable!(),
-                };
-
-                if p1 != 0 {
-                    *i = p2 as usize;
-                } else {
-                    *i += 3;
-                }
-            }
-            [0, 6] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[*i + 1] as usize],
-                    1 => tape[*i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[*i + 2] as usize],
-                    1 => tape[*i + 2],
-                    _ => unreachable!(),
-                };
-
-                if p1 == 0 {
-                    *i = p2 as usize;
-                } else {
-                    *i += 3;
-                }
-            }
-            [0, 7] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[*i + 1] as usize],
-                    1 => tape[*i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[*i + 2] as usize],
-                    1 => tape[*i + 2],
-                    _ => unreachable!(),
-                };
-
-                let output = tape[*i + 3] as usize;
-
-                if p1 < p2 {
-                    tape[output] = 1;
-                } else {
-                    tape[output] = 0;
-                }
-                *i += 4;
-            }
-            [0, 8] => {
-                let p1 = digits.get(digits.len() - 3).unwrap_or(&0);
-                let p2 = digits.get(digits.len() - 4).unwrap_or(&0);
-
-                let p1 = match p1 {
-                    0 => tape[tape[*i + 1] as usize],
-                    1 => tape[*i + 1],
-                    _ => unreachable!(),
-                };
-                let p2 = match p2 {
-                    0 => tape[tape[*i + 2] as usize],
-                    1 => tape[*i + 2],
-                    _ => unreachable!(),
-                };
-
-                let output = tape[*i + 3] as usize;
-
-                if p1 == p2 {
-                    tape[output] = 1;
-                } else {
-                    tape[output] = 0;
-                }
-                *i += 4;
-            }
-            [9, 9] => break -1,
-            _ => panic!("Unrecognized opcode: {:?}", opcode),
-        };
-    }
+fn solve_p2(tape: &[i64]) -> i64 {
+    best_signal(tape, 5..=9, true)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn day7_chain() {
+        let tape = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        assert_eq!(amplify(&tape, &[4, 3, 2, 1, 0], false), 43210);
+        assert_eq!(solve_p1(&tape), 43210);
+    }
+
     #[test]
     fn day7_sample1() {
         let tape = vec![
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// `(x, y)`, with `y` going down the page like the puzzle inputs
+pub type Pos = (usize, usize);
+
+/// Up, down, left and right
+pub const ORTHOGONAL: [(i64, i64); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+/// The orthogonal ones, then the diagonals clockwise from up and to the right
+pub const ALL_AROUND: [(i64, i64); 8] = [
+    (0, -1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+    (1, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Something a map is made of, which is drawn as a single character
+pub trait Tile: Sized {
+    /// `None` if `c` isn't a tile of this kind
+    fn from_char(c: char) -> Option<Self>;
+
+    fn to_char(&self) -> char;
+}
+
+impl Tile for char {
+    fn from_char(c: char) -> Option<char> {
+        Some(c)
+    }
+
+    fn to_char(&self) -> char {
+        *self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    UnknownTile {
+        pos: Pos,
+        c: char,
+    },
+    /// A line that isn't as long as the first one
+    Ragged {
+        line: usize,
+    },
+    Empty,
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::UnknownTile { pos, c } => write!(f, "unknown tile {:?} at {:?}", c, pos),
+            GridError::Ragged { line } => {
+                write!(f, "line {} isn't the same length as the first", line + 1)
+            }
+            GridError::Empty => write!(f, "there's no map"),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+/// A rectangle of tiles, stored row by row
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Grid<T> {
+    tiles: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid {
+            tiles: vec![fill; width * height],
+            width,
+            height,
+        }
+    }
+
+    /// Fills in each tile from its position, row by row
+    pub fn from_fn<F: FnMut(Pos) -> T>(width: usize, height: usize, f: F) -> Grid<T> {
+        let tiles = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(f)
+            .collect();
+        Grid {
+            tiles,
+            width,
+            height,
+        }
+    }
+
+    /// One tile per character and one row per line. Every line has to be the same length.
+    pub fn parse(input: &str) -> Result<Grid<T>, GridError>
+    where
+        T: Tile,
+    {
+        let width = input
+            .lines()
+            .next()
+            .ok_or(GridError::Empty)?
+            .chars()
+            .count();
+        let mut tiles = Vec::new();
+        let mut height = 0;
+
+        for (y, line) in input.lines().enumerate() {
+            if line.chars().count() != width {
+                return Err(GridError::Ragged { line: y });
+            }
+            for (x, c) in line.chars().enumerate() {
+                let tile = T::from_char(c).ok_or(GridError::UnknownTile { pos: (x, y), c })?;
+                tiles.push(tile);
+            }
+            height += 1;
+        }
+
+        Ok(Grid {
+            tiles,
+            width,
+            height,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, pos: Pos) -> bool {
+        pos.0 < self.width && pos.1 < self.height
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&T> {
+        if self.contains(pos) {
+            self.tiles.get(pos.1 * self.width + pos.0)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+        if self.contains(pos) {
+            self.tiles.get_mut(pos.1 * self.width + pos.0)
+        } else {
+            None
+        }
+    }
+
+    /// Every tile along with where it is, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        let width = self.width;
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(move |(i, tile)| ((i % width, i / width), tile))
+    }
+
+    /// Where the first tile that matches is, going row by row
+    pub fn position<P: FnMut(&T) -> bool>(&self, mut predicate: P) -> Option<Pos> {
+        self.iter()
+            .find(|(_, tile)| predicate(tile))
+            .map(|(pos, _)| pos)
+    }
+
+    /// The same shape of grid, with every tile changed by `f`
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            tiles: self.tiles.iter().map(f).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// The position `(dx, dy)` away, unless that's off the edge
+    pub fn offset(&self, pos: Pos, (dx, dy): (i64, i64)) -> Option<Pos> {
+        let x = pos.0 as i64 + dx;
+        let y = pos.1 as i64 + dy;
+        if x < 0 || y < 0 {
+            return None;
+        }
+        Some((x as usize, y as usize)).filter(|&pos| self.contains(pos))
+    }
+
+    /// The positions up, down, left and right of `pos` that are on the grid
+    pub fn neighbours4(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        ORTHOGONAL
+            .iter()
+            .filter_map(move |&dir| self.offset(pos, dir))
+    }
+
+    /// Like `neighbours4`, but with the diagonals too
+    pub fn neighbours8(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        ALL_AROUND
+            .iter()
+            .filter_map(move |&dir| self.offset(pos, dir))
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: Pos) -> &T {
+        self.get(pos)
+            .unwrap_or_else(|| panic!("{:?} is off the grid", pos))
+    }
+}
+
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, pos: Pos) -> &mut T {
+        self.get_mut(pos)
+            .unwrap_or_else(|| panic!("{:?} is off the grid", pos))
+    }
+}
+
+/// Draws the grid the way it was parsed, with a newline after every row
+impl<T: Tile> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.tiles.chunks(self.width.max(1)) {
+            for tile in row {
+                write!(f, "{}", tile.to_char())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// For maps that get discovered a bit at a time, and don't have a fixed size or even a
+/// corner to start from
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SparseGrid<T> {
+    tiles: HashMap<(i64, i64), T>,
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> SparseGrid<T> {
+        SparseGrid {
+            tiles: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn insert(&mut self, pos: (i64, i64), tile: T) -> Option<T> {
+        self.tiles.insert(pos, tile)
+    }
+
+    pub fn contains(&self, pos: (i64, i64)) -> bool {
+        self.tiles.contains_key(&pos)
+    }
+
+    pub fn get(&self, pos: (i64, i64)) -> Option<&T> {
+        self.tiles.get(&pos)
+    }
+
+    pub fn get_mut(&mut self, pos: (i64, i64)) -> Option<&mut T> {
+        self.tiles.get_mut(&pos)
+    }
+
+    /// Every tile that's been filled in, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        self.tiles.iter().map(|(&pos, tile)| (pos, tile))
+    }
+
+    /// The top left and bottom right corners of everything filled in so far
+    pub fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let xs = self.tiles.keys().map(|pos| pos.0);
+        let ys = self.tiles.keys().map(|pos| pos.1);
+        Some((
+            (xs.clone().min()?, ys.clone().min()?),
+            (xs.max()?, ys.max()?),
+        ))
+    }
+
+    /// The positions up, down, left and right of `pos`, whether they're filled in or not
+    pub fn neighbours4(&self, pos: (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+        ORTHOGONAL.iter().map(move |d| (pos.0 + d.0, pos.1 + d.1))
+    }
+
+    /// Like `neighbours4`, but with the diagonals too
+    pub fn neighbours8(&self, pos: (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+        ALL_AROUND.iter().map(move |d| (pos.0 + d.0, pos.1 + d.1))
+    }
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> SparseGrid<T> {
+        SparseGrid::new()
+    }
+}
+
+impl<T> Index<(i64, i64)> for SparseGrid<T> {
+    type Output = T;
+
+    fn index(&self, pos: (i64, i64)) -> &T {
+        self.get(pos)
+            .unwrap_or_else(|| panic!("Nothing at {:?} yet", pos))
+    }
+}
+
+/// Draws everything inside the bounds, with spaces for the parts that aren't filled in
+impl<T: Tile> fmt::Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ((left, top), (right, bottom)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+        for y in top..=bottom {
+            for x in left..=right {
+                write!(f, "{}", self.get((x, y)).map_or(' ', Tile::to_char))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    enum Cell {
+        Open,
+        Wall,
+    }
+
+    impl Tile for Cell {
+        fn from_char(c: char) -> Option<Cell> {
+            match c {
+                '.' => Some(Cell::Open),
+                '#' => Some(Cell::Wall),
+                _ => None,
+            }
+        }
+
+        fn to_char(&self) -> char {
+            match self {
+                Cell::Open => '.',
+                Cell::Wall => '#',
+            }
+        }
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let text = "#..\n.#.\n..#\n.##\n";
+        let grid: Grid<Cell> = Grid::parse(text).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 4));
+        assert_eq!(grid[(1, 3)], Cell::Wall);
+        assert_eq!(grid.get((3, 0)), None);
+        assert_eq!(grid.position(|&c| c == Cell::Open), Some((1, 0)));
+        assert_eq!(grid.to_string(), text);
+
+        assert_eq!(
+            Grid::<Cell>::parse("#.\n#?"),
+            Err(GridError::UnknownTile {
+                pos: (1, 1),
+                c: '?'
+            })
+        );
+        assert_eq!(
+            Grid::<Cell>::parse("#.\n#\n.."),
+            Err(GridError::Ragged { line: 1 })
+        );
+        assert_eq!(Grid::<char>::parse(""), Err(GridError::Empty));
+    }
+
+    #[test]
+    fn neighbours() {
+        let grid = Grid::from_fn(3, 2, |(x, y)| x + y);
+        let around = |pos| grid.neighbours4(pos).collect::<Vec<_>>();
+        assert_eq!(around((0, 0)), vec![(0, 1), (1, 0)]);
+        assert_eq!(around((1, 1)), vec![(1, 0), (0, 1), (2, 1)]);
+        assert_eq!(grid.neighbours8((1, 0)).count(), 5);
+        assert_eq!(
+            grid.neighbours8((2, 1)).map(|pos| grid[pos]).sum::<usize>(),
+            5
+        );
+    }
+
+    #[test]
+    fn sparse() {
+        let mut grid = SparseGrid::new();
+        assert_eq!(grid.to_string(), "");
+        grid.insert((-2, -1), Cell::Wall);
+        grid.insert((0, 0), Cell::Open);
+        grid.insert((0, 1), Cell::Wall);
+        assert_eq!(grid.bounds(), Some(((-2, -1), (0, 1))));
+        assert_eq!(grid.to_string(), "#  \n  .\n  #\n");
+        assert_eq!(
+            grid.neighbours4((0, 0))
+                .filter(|&pos| grid.get(pos) == Some(&Cell::Wall))
+                .count(),
+            1
+        );
+        assert_eq!(
+            grid.neighbours8((-1, 0))
+                .filter(|&pos| grid.contains(pos))
+                .count(),
+            3
+        );
+    }
+}
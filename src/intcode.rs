@@ -0,0 +1,1446 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::{fs, io};
+
+pub mod ascii;
+pub mod asm;
+pub mod capture;
+pub mod cell;
+pub mod compile;
+pub mod debugger;
+pub mod disasm;
+pub mod memory;
+pub mod network;
+pub mod ports;
+pub mod topology;
+
+pub use cell::Cell;
+pub use compile::Backend;
+pub use memory::Memory;
+pub use ports::{FromFn, Input, Output};
+pub use topology::{MachineError, Topology};
+
+/// Addresses past this fault with `AddressLimit` unless the limit is changed. That's 128MB
+/// of flat memory, which is far more than any puzzle needs.
+pub const DEFAULT_ADDRESS_LIMIT: usize = 1 << 24;
+
+pub fn parse_program(input: &str) -> Vec<i64> {
+    input
+        .trim()
+        .split(',')
+        .map(|i| i.trim().parse::<i64>().unwrap())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Arb,
+    Hlt,
+}
+
+pub const OPCODES: [Opcode; 10] = [
+    Opcode::Add,
+    Opcode::Mul,
+    Opcode::In,
+    Opcode::Out,
+    Opcode::Jnz,
+    Opcode::Jz,
+    Opcode::Lt,
+    Opcode::Eq,
+    Opcode::Arb,
+    Opcode::Hlt,
+];
+
+impl Opcode {
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        OPCODES.iter().copied().find(|op| op.code() == code)
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        OPCODES.iter().copied().find(|op| op.mnemonic() == mnemonic)
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::In => 3,
+            Opcode::Out => 4,
+            Opcode::Jnz => 5,
+            Opcode::Jz => 6,
+            Opcode::Lt => 7,
+            Opcode::Eq => 8,
+            Opcode::Arb => 9,
+            Opcode::Hlt => 99,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::In => "in",
+            Opcode::Out => "out",
+            Opcode::Jnz => "jnz",
+            Opcode::Jz => "jz",
+            Opcode::Lt => "lt",
+            Opcode::Eq => "eq",
+            Opcode::Arb => "arb",
+            Opcode::Hlt => "hlt",
+        }
+    }
+
+    pub fn param_count(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => 3,
+            Opcode::Jnz | Opcode::Jz => 2,
+            Opcode::In | Opcode::Out | Opcode::Arb => 1,
+            Opcode::Hlt => 0,
+        }
+    }
+
+    /// Which parameter (starting from 0) the instruction stores its result in
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => Some(2),
+            Opcode::In => Some(0),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    pub fn from_digit(digit: i64) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+/// An opcode along with the modes of its parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: [Mode; 3],
+}
+
+impl Instruction {
+    /// Returns `None` for anything the VM would refuse to execute. Also `None` when there's
+    /// a mode for a parameter the opcode doesn't have: the VM ignores those, but `encode`
+    /// couldn't give the same value back.
+    pub fn decode(value: i64) -> Option<Instruction> {
+        if !(0..100_000).contains(&value) {
+            return None;
+        }
+        let opcode = Opcode::from_code(value % 100)?;
+        let mut modes = [Mode::Position; 3];
+        for (i, mode) in modes.iter_mut().enumerate() {
+            let digit = value / 10_i64.pow(i as u32 + 2) % 10;
+            if i >= opcode.param_count() && digit != 0 {
+                return None;
+            }
+            *mode = Mode::from_digit(digit)?;
+        }
+        if let Some(w) = opcode.write_param() {
+            if modes[w] == Mode::Immediate {
+                return None;
+            }
+        }
+        Some(Instruction { opcode, modes })
+    }
+
+    pub fn encode(&self) -> i64 {
+        self.modes
+            .iter()
+            .enumerate()
+            .map(|(i, mode)| mode.digit() * 10_i64.pow(i as u32 + 2))
+            .sum::<i64>()
+            + self.opcode.code()
+    }
+
+    /// How many cells the instruction takes up, including itself
+    pub fn size(&self) -> usize {
+        self.opcode.param_count() + 1
+    }
+}
+
+/// What the VM was doing when it stopped running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State<C = i64> {
+    Output(C),
+    NeedInput,
+    Halted,
+}
+
+/// What arithmetic does when a result doesn't fit in a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    Wrap,
+    /// Fault with `VmError::Overflow` instead
+    Fail,
+}
+
+/// Everything that can make a program fault. `ip` is always the address of the instruction
+/// that was executing. Values that don't fit in an `i64` are clamped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    InvalidOpcode {
+        ip: usize,
+        opcode: i64,
+    },
+    BadMode {
+        ip: usize,
+        mode: u64,
+    },
+    ImmediateWrite {
+        ip: usize,
+    },
+    NegativeAddress {
+        ip: usize,
+        address: i64,
+    },
+    AddressLimit {
+        ip: usize,
+        address: i64,
+        limit: usize,
+    },
+    Overflow {
+        ip: usize,
+    },
+    StepLimitExceeded {
+        ip: usize,
+        limit: u64,
+    },
+    OutOfInput {
+        ip: usize,
+    },
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::InvalidOpcode { ip, opcode } => {
+                write!(f, "invalid opcode {} at address {}", opcode, ip)
+            }
+            VmError::BadMode { ip, mode } => {
+                write!(f, "unrecognized parameter mode {} at address {}", mode, ip)
+            }
+            VmError::ImmediateWrite { ip } => {
+                write!(f, "write to an immediate mode parameter at address {}", ip)
+            }
+            VmError::NegativeAddress { ip, address } => {
+                write!(f, "negative address {} used at address {}", address, ip)
+            }
+            VmError::AddressLimit { ip, address, limit } => write!(
+                f,
+                "address {} used at address {} is past the limit of {}",
+                address, ip, limit
+            ),
+            VmError::Overflow { ip } => write!(f, "arithmetic overflow at address {}", ip),
+            VmError::StepLimitExceeded { ip, limit } => {
+                write!(f, "gave up after {} steps at address {}", limit, ip)
+            }
+            VmError::OutOfInput { ip } => {
+                write!(f, "program needed more input at address {}", ip)
+            }
+        }
+    }
+}
+
+impl Error for VmError {}
+
+/// Which kinds of memory access a watchpoint fires on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn includes(self, access: Access) -> bool {
+        self == Access::ReadWrite || self == access
+    }
+}
+
+/// A watched address being read or written. `value` is what was read, or the new value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit<C = i64> {
+    pub ip: usize,
+    pub address: usize,
+    pub access: Access,
+    pub value: C,
+}
+
+/// One executed instruction. `operands` holds the value each parameter had, except that a
+/// parameter being written to holds the address instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent<C = i64> {
+    pub ip: usize,
+    pub opcode: Opcode,
+    pub operands: Vec<C>,
+    pub write: Option<(usize, C)>,
+    pub output: Option<C>,
+}
+
+impl<C: fmt::Display> fmt::Display for TraceEvent<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}: {}", self.ip, self.opcode.mnemonic())?;
+        if !self.operands.is_empty() {
+            let operands: Vec<_> = self.operands.iter().map(|o| o.to_string()).collect();
+            let pad = 4 - self.opcode.mnemonic().len();
+            write!(f, "{:pad$} {}", "", operands.join(", "), pad = pad)?;
+        }
+        if let Some((address, value)) = &self.write {
+            write!(f, " ; [{}] <- {}", address, value)?;
+        }
+        if let Some(output) = &self.output {
+            write!(f, " ; output {}", output)?;
+        }
+        Ok(())
+    }
+}
+
+pub type TraceHook<C = i64> = Box<dyn FnMut(&TraceEvent<C>) + Send>;
+
+/// A parameter once its mode has been taken into account
+#[derive(Debug, Clone)]
+enum Param<C> {
+    Immediate(C),
+    Address(i64),
+}
+
+/// Everything needed to put a machine back the way it was. Memory is shared with the
+/// machine until one of them writes to it, so taking a snapshot is cheap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<C: Cell = i64> {
+    memory: Arc<Memory<C>>,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<C>,
+    output: VecDeque<C>,
+    steps: u64,
+}
+
+const SNAPSHOT_HEADER: &str = "intcode snapshot v1";
+
+fn join<T: fmt::Display>(values: impl IntoIterator<Item = T>) -> String {
+    let values: Vec<_> = values.into_iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+impl<C: Cell> Snapshot<C> {
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Snapshot<C>> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<C: Cell> fmt::Display for Snapshot<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", SNAPSHOT_HEADER)?;
+        writeln!(f, "ip {}", self.ip)?;
+        writeln!(f, "relative_base {}", self.relative_base)?;
+        writeln!(f, "steps {}", self.steps)?;
+        let lists = [("input", join(&self.input)), ("output", join(&self.output))];
+        for (name, values) in &lists {
+            if values.is_empty() {
+                writeln!(f, "{}", name)?;
+            } else {
+                writeln!(f, "{} {}", name, values)?;
+            }
+        }
+
+        // paged memory is written a page at a time, so that the gaps don't take up space
+        if self.memory.is_paged() {
+            writeln!(f, "paged_memory")?;
+            for (start, values) in self.memory.chunks() {
+                writeln!(f, "page {} {}", start, join(values))?;
+            }
+        } else {
+            let values = join(self.memory.to_vec());
+            if values.is_empty() {
+                writeln!(f, "memory")?;
+            } else {
+                writeln!(f, "memory {}", values)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: Cell> FromStr for Snapshot<C> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Snapshot<C>, String> {
+        let mut lines = s.lines();
+        if lines.next() != Some(SNAPSHOT_HEADER) {
+            return Err("not an Intcode snapshot".to_owned());
+        }
+
+        let mut field = |name: &str| {
+            let line = lines.next().unwrap_or("");
+            if line == name {
+                Ok("")
+            } else if line.starts_with(name) && line[name.len()..].starts_with(' ') {
+                Ok(line[name.len() + 1..].trim())
+            } else {
+                Err(format!("expected {}, found {:?}", name, line))
+            }
+        };
+        fn number<T: FromStr>(value: &str) -> Result<T, String> {
+            value.parse().map_err(|_| format!("bad number {:?}", value))
+        }
+        fn list<C: Cell>(value: &str) -> Result<Vec<C>, String> {
+            if value.is_empty() {
+                return Ok(Vec::new());
+            }
+            value.split(',').map(number).collect()
+        }
+
+        let ip = number(field("ip")?)?;
+        let relative_base = number(field("relative_base")?)?;
+        let steps = number(field("steps")?)?;
+        let input = list(field("input")?)?.into();
+        let output = list(field("output")?)?.into();
+
+        let memory = match lines.next() {
+            Some("paged_memory") => {
+                let mut memory = Memory::paged(&[]);
+                for line in lines {
+                    let mut parts = line.splitn(3, ' ');
+                    if parts.next() != Some("page") {
+                        return Err(format!("expected page, found {:?}", line));
+                    }
+                    let start: usize = number(parts.next().unwrap_or(""))?;
+                    for (offset, value) in list(parts.next().unwrap_or(""))?.into_iter().enumerate()
+                    {
+                        memory.set(start + offset, value);
+                    }
+                }
+                memory
+            }
+            Some("memory") => Memory::flat(&[]),
+            Some(line) if line.starts_with("memory ") => Memory::flat(&list(&line[7..])?),
+            line => return Err(format!("expected memory, found {:?}", line)),
+        };
+
+        Ok(Snapshot {
+            memory: Arc::new(memory),
+            ip,
+            relative_base,
+            input,
+            output,
+            steps,
+        })
+    }
+}
+
+/// Instructions past this address are decoded every time rather than cached, so a program
+/// running out in paged memory doesn't make the cache enormous
+const CACHE_LIMIT: usize = 1 << 16;
+
+/// An instruction along with the raw values of its parameters, straight from memory
+#[derive(Debug, Clone)]
+struct Decoded<C> {
+    instr: Instruction,
+    args: [C; 3],
+}
+
+/// Everything needed to take back one executed instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Undo<C> {
+    ip: usize,
+    relative_base: i64,
+    memory_len: usize,
+    overwritten: Option<(usize, C)>,
+    input: Option<C>,
+    output: Option<C>,
+}
+
+/// A resumable Intcode machine. Input is queued with `input`, and `run` executes until the
+/// program produces an output, asks for input that hasn't been queued, or halts.
+///
+/// Memory holds `i64`s unless it's built with another `Cell` type, e.g.
+/// `Vm::with_memory(Memory::<BigInt>::flat(..))` for programs whose numbers get out of hand.
+pub struct Vm<C: Cell = i64> {
+    memory: Arc<Memory<C>>,
+    address_limit: Option<usize>,
+    overflow: Overflow,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<C>,
+    output: VecDeque<C>,
+    steps: u64,
+    step_limit: Option<u64>,
+    trace_hook: Option<TraceHook<C>>,
+    watchpoints: HashMap<usize, Access>,
+    watch_hits: Vec<WatchHit<C>>,
+    last_write: Option<(usize, C)>,
+    history: Option<Vec<Undo<C>>>,
+    overwritten: Option<(usize, C)>,
+    cache: Option<Vec<Option<Decoded<C>>>>,
+    blocks: Option<compile::Blocks<C>>,
+}
+
+impl Vm {
+    /// A machine with flat memory, which suits almost every program
+    pub fn new(tape: &[i64]) -> Vm {
+        Vm::with_memory(Memory::flat(tape))
+    }
+}
+
+impl<C: Cell> Vm<C> {
+    /// A machine using `memory`, e.g. `Memory::paged` for programs that scatter writes far
+    /// and wide
+    pub fn with_memory(memory: Memory<C>) -> Vm<C> {
+        Vm {
+            memory: Arc::new(memory),
+            address_limit: Some(DEFAULT_ADDRESS_LIMIT),
+            overflow: Overflow::Wrap,
+            ip: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            steps: 0,
+            step_limit: None,
+            trace_hook: None,
+            watchpoints: HashMap::new(),
+            watch_hits: Vec::new(),
+            last_write: None,
+            history: None,
+            overwritten: None,
+            cache: Some(Vec::new()),
+            blocks: None,
+        }
+    }
+
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    pub fn into_tape(self) -> Vec<C> {
+        self.memory.to_vec()
+    }
+
+    /// Makes the program fault with `AddressLimit` when it uses an address past `limit`,
+    /// rather than growing memory to reach it
+    pub fn set_address_limit(&mut self, limit: Option<usize>) {
+        self.address_limit = limit;
+        // compiled code has its addresses checked against the old limit
+        if self.blocks.is_some() {
+            self.set_backend(Backend::Compiled);
+        }
+    }
+
+    /// Arithmetic wraps around by default. The relative base is always checked, since it
+    /// has to fit in an `i64` whatever the cell type is.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// Saves the machine's state, including any input or output that's still queued up.
+    /// Hooks and watchpoints aren't part of it.
+    pub fn snapshot(&self) -> Snapshot<C> {
+        Snapshot {
+            memory: Arc::clone(&self.memory),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            steps: self.steps,
+        }
+    }
+
+    /// Puts the machine back into a saved state. Any recorded history is thrown away,
+    /// since it describes how the old state came about.
+    pub fn restore(&mut self, snapshot: &Snapshot<C>) {
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        self.clear_cache();
+        if let Some(blocks) = &mut self.blocks {
+            blocks.memory_replaced();
+        }
+        self.memory = Arc::clone(&snapshot.memory);
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.steps = snapshot.steps;
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot<C>) -> Vm<C> {
+        let mut vm = Vm::with_memory(Memory::flat(&[]));
+        vm.restore(snapshot);
+        vm
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// How many instructions have been executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Makes the machine fail with `StepLimitExceeded` instead of running forever
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    /// Reads memory the same way the program would, so anything past the end is 0
+    pub fn peek(&self, address: usize) -> C {
+        self.read(address)
+    }
+
+    /// Writes memory the same way the program would, so it's held to the same address limit
+    pub fn poke(&mut self, address: usize, value: C) -> Result<(), VmError> {
+        // a program can't get past i64::MAX, so nothing from outside can either
+        let address = address.try_into().map_err(|_| VmError::AddressLimit {
+            ip: self.ip,
+            address: i64::MAX,
+            limit: i64::MAX as usize,
+        })?;
+        let address = self.to_address(address)?;
+        self.write(address, value);
+        Ok(())
+    }
+
+    /// Calls `hook` after every instruction that gets executed
+    pub fn set_trace_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&TraceEvent<C>) + Send + 'static,
+    {
+        self.trace_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+    }
+
+    pub fn watch(&mut self, address: usize, access: Access) {
+        self.watchpoints.insert(address, access);
+    }
+
+    pub fn unwatch(&mut self, address: usize) {
+        self.watchpoints.remove(&address);
+    }
+
+    /// Every time a watched address was touched since this was last called
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit<C>> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    /// Starts or stops remembering how to undo each instruction. This costs memory for
+    /// every step, so it's off unless something like the debugger wants to go backwards.
+    pub fn record_history(&mut self, enabled: bool) {
+        self.history = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// How many instructions `step_back` can currently undo
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Undoes the last executed instruction, putting back anything it overwrote and any
+    /// input it consumed. Outputs have already been handed out, so those stay wherever they
+    /// went. Returns `false` if there's no history left.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.as_mut().and_then(Vec::pop) {
+            Some(undo) => undo,
+            None => return false,
+        };
+
+        if let Some((address, old)) = undo.overwritten {
+            self.write(address, old);
+        }
+        Arc::make_mut(&mut self.memory).truncate(undo.memory_len);
+        if let Some(input) = undo.input {
+            self.input.push_front(input);
+        }
+        self.ip = undo.ip;
+        self.relative_base = undo.relative_base;
+        self.steps -= 1;
+        true
+    }
+
+    /// Steps back until just before the most recent instruction that matches `wanted`,
+    /// leaving the machine alone if nothing in the recorded history does
+    fn reverse_until<F>(&mut self, wanted: F) -> Option<Undo<C>>
+    where
+        F: Fn(&Undo<C>) -> bool,
+    {
+        let history = self.history.as_ref()?;
+        let idx = history.iter().rposition(wanted)?;
+        let found = history[idx].clone();
+        while self.history_len() > idx {
+            self.step_back();
+        }
+        Some(found)
+    }
+
+    /// Rewinds to just before the last instruction that wrote to `address`. Returns
+    /// `false` if the recorded history never wrote there.
+    pub fn rewind_to_write(&mut self, address: usize) -> bool {
+        self.reverse_until(|undo| match undo.overwritten {
+            Some((written, _)) => written == address,
+            None => false,
+        })
+        .is_some()
+    }
+
+    /// Rewinds to just before the last output instruction, returning what it printed
+    pub fn reverse_to_output(&mut self) -> Option<C> {
+        self.reverse_until(|undo| undo.output.is_some())?.output
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<C> {
+        &self.input
+    }
+
+    pub fn input(&mut self, value: C) {
+        self.input.push_back(value);
+    }
+
+    pub fn input_all<I: IntoIterator<Item = C>>(&mut self, values: I) {
+        self.input.extend(values);
+    }
+
+    /// Takes every output that `run_until_blocked` has buffered so far
+    pub fn drain_output(&mut self) -> Vec<C> {
+        self.output.drain(..).collect()
+    }
+
+    /// Runs until the next output, or until the program needs input or halts
+    pub fn run(&mut self) -> Result<State<C>, VmError> {
+        loop {
+            if let Some(state) = self.run_compiled()? {
+                break Ok(state);
+            }
+            if let Some(state) = self.step()? {
+                break Ok(state);
+            }
+        }
+    }
+
+    /// Like `run`, but calls `get_input` instead of stopping when the input queue is empty
+    pub fn run_with<F>(&mut self, mut get_input: F) -> Result<State<C>, VmError>
+    where
+        F: FnMut() -> C,
+    {
+        loop {
+            match self.run()? {
+                State::NeedInput => self.input(get_input()),
+                state => break Ok(state),
+            }
+        }
+    }
+
+    /// Runs until the program needs input or halts, buffering any output it produces
+    pub fn run_until_blocked(&mut self) -> Result<State<C>, VmError> {
+        loop {
+            match self.run()? {
+                State::Output(out) => self.output.push_back(out),
+                state => break Ok(state),
+            }
+        }
+    }
+
+    /// Runs the program to completion, returning everything it printed
+    pub fn run_to_halt(&mut self) -> Result<Vec<C>, VmError> {
+        match self.run_until_blocked()? {
+            State::Halted => Ok(self.drain_output()),
+            _ => Err(VmError::OutOfInput { ip: self.ip }),
+        }
+    }
+
+    fn read(&self, address: usize) -> C {
+        self.memory.get(address)
+    }
+
+    fn write(&mut self, address: usize, value: C) {
+        Arc::make_mut(&mut self.memory).set(address, value);
+        if let Some(blocks) = &mut self.blocks {
+            blocks.invalidate(address);
+        }
+
+        // any instruction this could be part of has to be decoded again
+        if let Some(cache) = &mut self.cache {
+            let end = address.saturating_add(1).min(cache.len());
+            for decoded in &mut cache[address.saturating_sub(3).min(end)..end] {
+                *decoded = None;
+            }
+        }
+    }
+
+    /// Turns caching of decoded instructions on or off. It's on by default, and only worth
+    /// turning off to see how much it helps.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    fn clear_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            for decoded in cache.iter_mut() {
+                *decoded = None;
+            }
+        }
+    }
+
+    /// Checks that an address the program came up with is one it's allowed to use
+    fn to_address(&self, address: i64) -> Result<usize, VmError> {
+        let ip = self.ip;
+        let checked: usize = address
+            .try_into()
+            .map_err(|_| VmError::NegativeAddress { ip, address })?;
+        match self.address_limit {
+            Some(limit) if checked >= limit => Err(VmError::AddressLimit { ip, address, limit }),
+            _ => Ok(checked),
+        }
+    }
+
+    /// Where a relative parameter points. Anything that can't be an `i64` is clamped, which
+    /// puts it out of bounds one way or the other.
+    fn relative_address(&self, offset: &C) -> i64 {
+        offset
+            .to_i64()
+            .and_then(|offset| self.relative_base.checked_add(offset))
+            .unwrap_or_else(|| {
+                if *offset < C::zero() {
+                    i64::MIN
+                } else {
+                    i64::MAX
+                }
+            })
+    }
+
+    /// Adds or multiplies, depending on `opcode`
+    fn arithmetic(&self, opcode: Opcode, a: &C, b: &C) -> Result<C, VmError> {
+        let result = match (opcode, self.overflow) {
+            (Opcode::Add, Overflow::Wrap) => Some(a.wrapping_add(b)),
+            (Opcode::Add, Overflow::Fail) => a.checked_add(b),
+            (_, Overflow::Wrap) => Some(a.wrapping_mul(b)),
+            (_, Overflow::Fail) => a.checked_mul(b),
+        };
+        result.ok_or(VmError::Overflow { ip: self.ip })
+    }
+
+    fn adjust_relative_base(&mut self, by: &C) -> Result<(), VmError> {
+        self.relative_base = by
+            .to_i64()
+            .and_then(|by| self.relative_base.checked_add(by))
+            .ok_or(VmError::Overflow { ip: self.ip })?;
+        Ok(())
+    }
+
+    fn watched(&mut self, address: usize, access: Access, value: &C) {
+        if let Some(watch) = self.watchpoints.get(&address) {
+            if watch.includes(access) {
+                self.watch_hits.push(WatchHit {
+                    ip: self.ip,
+                    address,
+                    access,
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+
+    fn load(&mut self, param: &Param<C>) -> Result<C, VmError> {
+        match param {
+            Param::Immediate(value) => Ok(value.clone()),
+            Param::Address(address) => {
+                let address = self.to_address(*address)?;
+                let value = self.read(address);
+                self.watched(address, Access::Read, &value);
+                Ok(value)
+            }
+        }
+    }
+
+    fn store(&mut self, param: &Param<C>, value: C) -> Result<(), VmError> {
+        match param {
+            Param::Immediate(_) => Err(VmError::ImmediateWrite { ip: self.ip }),
+            Param::Address(address) => {
+                let address = self.to_address(*address)?;
+                if self.history.is_some() {
+                    self.overwritten = Some((address, self.read(address)));
+                }
+                self.watched(address, Access::Write, &value);
+                self.last_write = Some((address, value.clone()));
+                self.write(address, value);
+                Ok(())
+            }
+        }
+    }
+
+    fn decode_uncached(&self, ip: usize) -> Result<Decoded<C>, VmError> {
+        let cell = self.read(ip);
+        let invalid = VmError::InvalidOpcode {
+            ip,
+            opcode: cell.saturating_i64(),
+        };
+        let value = cell.to_i64().ok_or_else(|| invalid.clone())?;
+        let opcode = match Opcode::from_code(value % 100) {
+            Some(opcode) if value >= 0 => opcode,
+            _ => return Err(invalid),
+        };
+
+        let mut instr = Instruction {
+            opcode,
+            modes: [Mode::Position; 3],
+        };
+        let mut args = [C::zero(), C::zero(), C::zero()];
+        let mut modes = value / 100;
+        let params = instr.modes.iter_mut().zip(args.iter_mut());
+        for (idx, (mode, arg)) in params.enumerate().take(opcode.param_count()) {
+            *mode = Mode::from_digit(modes % 10).ok_or(VmError::BadMode {
+                ip,
+                mode: (modes % 10) as u64,
+            })?;
+            *arg = self.read(ip + idx + 1);
+            modes /= 10;
+        }
+
+        Ok(Decoded { instr, args })
+    }
+
+    fn decode(&mut self, ip: usize) -> Result<(Opcode, [Param<C>; 3]), VmError> {
+        let cached = match &self.cache {
+            Some(cache) => cache.get(ip).cloned().flatten(),
+            None => None,
+        };
+        let decoded = match cached {
+            Some(decoded) => decoded,
+            None => {
+                let decoded = self.decode_uncached(ip)?;
+                if let Some(cache) = &mut self.cache {
+                    if ip < CACHE_LIMIT {
+                        if ip >= cache.len() {
+                            cache.resize(self.memory.len().clamp(ip + 1, CACHE_LIMIT), None);
+                        }
+                        cache[ip] = Some(decoded.clone());
+                    }
+                }
+                decoded
+            }
+        };
+
+        let mut params = [Param::Address(0), Param::Address(0), Param::Address(0)];
+        let args = decoded.args.iter().zip(&decoded.instr.modes);
+        for (param, (arg, mode)) in params.iter_mut().zip(args).take(decoded.instr.size() - 1) {
+            *param = match mode {
+                Mode::Position => Param::Address(arg.saturating_i64()),
+                Mode::Immediate => Param::Immediate(arg.clone()),
+                Mode::Relative => Param::Address(self.relative_address(arg)),
+            };
+        }
+
+        Ok((decoded.instr.opcode, params))
+    }
+
+    /// What each parameter looks like before the instruction runs, for tracing
+    fn operand_values(&self, opcode: Opcode, params: &[Param<C>; 3]) -> Vec<C> {
+        params
+            .iter()
+            .take(opcode.param_count())
+            .enumerate()
+            .map(|(idx, param)| match param {
+                Param::Immediate(value) => value.clone(),
+                Param::Address(address) if opcode.write_param() == Some(idx) => {
+                    C::from_i64(*address)
+                }
+                Param::Address(address) if *address >= 0 => self.read(*address as usize),
+                Param::Address(_) => C::zero(),
+            })
+            .collect()
+    }
+
+    /// Executes a single instruction. Returns `None` if the program can keep going.
+    pub fn step(&mut self) -> Result<Option<State<C>>, VmError> {
+        let ip = self.ip;
+
+        if let Some(limit) = self.step_limit {
+            if self.steps >= limit {
+                return Err(VmError::StepLimitExceeded { ip, limit });
+            }
+        }
+
+        let (opcode, params) = self.decode(ip)?;
+        let operands = if self.trace_hook.is_some() {
+            self.operand_values(opcode, &params)
+        } else {
+            Vec::new()
+        };
+
+        let relative_base = self.relative_base;
+        let memory_len = self.memory.len();
+        self.last_write = None;
+        self.overwritten = None;
+        let state = self.execute(opcode, &params)?;
+        if state == Some(State::NeedInput) {
+            return Ok(state);
+        }
+
+        let output = match &state {
+            Some(State::Output(out)) => Some(out.clone()),
+            _ => None,
+        };
+        if let Some(history) = &mut self.history {
+            history.push(Undo {
+                ip,
+                relative_base,
+                memory_len,
+                overwritten: self.overwritten.take(),
+                input: match (opcode, &self.last_write) {
+                    (Opcode::In, Some((_, input))) => Some(input.clone()),
+                    _ => None,
+                },
+                output: output.clone(),
+            });
+        }
+
+        self.steps += 1;
+        if let Some(hook) = &mut self.trace_hook {
+            hook(&TraceEvent {
+                ip,
+                opcode,
+                operands,
+                write: self.last_write.clone(),
+                output,
+            });
+        }
+
+        Ok(state)
+    }
+
+    fn execute(
+        &mut self,
+        opcode: Opcode,
+        params: &[Param<C>; 3],
+    ) -> Result<Option<State<C>>, VmError> {
+        match opcode {
+            Opcode::Add | Opcode::Mul => {
+                let p1 = self.load(&params[0])?;
+                let p2 = self.load(&params[1])?;
+
+                let result = self.arithmetic(opcode, &p1, &p2)?;
+                self.store(&params[2], result)?;
+                self.ip += 4;
+            }
+            Opcode::In => {
+                let input = match self.input.pop_front() {
+                    Some(input) => input,
+                    None => return Ok(Some(State::NeedInput)),
+                };
+                if let Err(e) = self.store(&params[0], input.clone()) {
+                    self.input.push_front(input);
+                    return Err(e);
+                }
+                self.ip += 2;
+            }
+            Opcode::Out => {
+                let out = self.load(&params[0])?;
+                self.ip += 2;
+                return Ok(Some(State::Output(out)));
+            }
+            Opcode::Jnz | Opcode::Jz => {
+                let zero = self.load(&params[0])? == C::zero();
+                if zero == (opcode == Opcode::Jz) {
+                    let target = self.load(&params[1])?;
+                    self.ip = self.to_address(target.saturating_i64())?;
+                } else {
+                    self.ip += 3;
+                }
+            }
+            Opcode::Lt | Opcode::Eq => {
+                let p1 = self.load(&params[0])?;
+                let p2 = self.load(&params[1])?;
+
+                let result = if opcode == Opcode::Lt {
+                    p1 < p2
+                } else {
+                    p1 == p2
+                };
+                self.store(&params[2], if result { C::one() } else { C::zero() })?;
+                self.ip += 4;
+            }
+            Opcode::Arb => {
+                let by = self.load(&params[0])?;
+                self.adjust_relative_base(&by)?;
+                self.ip += 2;
+            }
+            Opcode::Hlt => return Ok(Some(State::Halted)),
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_to_eight() {
+        // Day 5's example: outputs 999 below 8, 1000 at 8, and 1001 above 8
+        let tape = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for (input, expected) in &[(7, 999), (8, 1000), (9, 1001)] {
+            let mut vm = Vm::new(&tape);
+            vm.input(*input);
+            assert_eq!(vm.run_to_halt(), Ok(vec![*expected]));
+        }
+    }
+
+    #[test]
+    fn resumes_after_input() {
+        let mut vm = Vm::new(&[3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+        assert_eq!(vm.run(), Ok(State::NeedInput));
+        vm.input(-1);
+        assert_eq!(vm.run(), Ok(State::Output(-1)));
+        assert_eq!(vm.run(), Ok(State::NeedInput));
+        vm.input(5);
+        assert_eq!(vm.run(), Ok(State::Output(5)));
+        assert_eq!(vm.run(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn reports_faults() {
+        assert_eq!(
+            Vm::new(&[1, 0, 0, 0, 42]).run(),
+            Err(VmError::InvalidOpcode { ip: 4, opcode: 42 })
+        );
+        assert_eq!(
+            Vm::new(&[-1]).run(),
+            Err(VmError::InvalidOpcode { ip: 0, opcode: -1 })
+        );
+        assert_eq!(
+            Vm::new(&[301, 0, 0, 0]).run(),
+            Err(VmError::BadMode { ip: 0, mode: 3 })
+        );
+        assert_eq!(
+            Vm::new(&[11101, 1, 1, 0]).run(),
+            Err(VmError::ImmediateWrite { ip: 0 })
+        );
+        assert_eq!(
+            Vm::new(&[109, -5, 204, 0]).run(),
+            Err(VmError::NegativeAddress { ip: 2, address: -5 })
+        );
+    }
+
+    #[test]
+    fn trace() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&events);
+
+        let mut vm = Vm::new(&[1002, 6, 3, 6, 4, 6, 33]);
+        vm.set_trace_hook(move |event| recorder.lock().unwrap().push(event.clone()));
+        assert_eq!(vm.run(), Ok(State::Output(99)));
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                TraceEvent {
+                    ip: 0,
+                    opcode: Opcode::Mul,
+                    operands: vec![33, 3, 6],
+                    write: Some((6, 99)),
+                    output: None,
+                },
+                TraceEvent {
+                    ip: 4,
+                    opcode: Opcode::Out,
+                    operands: vec![99],
+                    write: None,
+                    output: Some(99),
+                },
+            ]
+        );
+        assert_eq!(events[0].to_string(), "0000: mul  33, 3, 6 ; [6] <- 99");
+    }
+
+    #[test]
+    fn watchpoints() {
+        // doubles [15] until it passes 100, then prints it
+        let tape = vec![
+            1002, 15, 2, 15, 1007, 15, 100, 16, 1005, 16, 0, 4, 15, 99, 0, 3, 0,
+        ];
+        let mut vm = Vm::new(&tape);
+        vm.watch(15, Access::Write);
+        vm.watch(16, Access::Read);
+        assert_eq!(vm.run(), Ok(State::Output(192)));
+
+        let hits = vm.take_watch_hits();
+        let writes: Vec<_> = hits
+            .iter()
+            .filter(|h| h.access == Access::Write)
+            .map(|h| h.value)
+            .collect();
+        assert_eq!(writes, vec![6, 12, 24, 48, 96, 192]);
+        assert!(hits
+            .iter()
+            .filter(|h| h.access == Access::Read)
+            .all(|h| h.address == 16 && h.ip == 8));
+        assert!(vm.take_watch_hits().is_empty());
+    }
+
+    #[test]
+    fn snapshots() {
+        let tape = vec![3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0];
+        let mut vm = Vm::new(&tape);
+        vm.input(5);
+        assert_eq!(vm.run(), Ok(State::Output(6)));
+
+        let saved = vm.snapshot();
+        vm.input(10);
+        assert_eq!(vm.run(), Ok(State::Output(11)));
+
+        // the snapshot didn't see the second write
+        assert_eq!(saved.memory()[11], 6);
+        vm.restore(&saved);
+        vm.input(20);
+        assert_eq!(vm.run(), Ok(State::Output(21)));
+
+        let mut branch = Vm::from_snapshot(&saved);
+        branch.input(30);
+        assert_eq!(branch.run(), Ok(State::Output(31)));
+    }
+
+    #[test]
+    fn snapshot_files() {
+        let mut vm = Vm::new(&[109, -3, 3, 10, 99]);
+        vm.input_all(vec![1, -2]);
+        vm.step().unwrap();
+        let saved = vm.snapshot();
+
+        let text = saved.to_string();
+        assert_eq!(
+            text,
+            "intcode snapshot v1
+ip 2
+relative_base -3
+steps 1
+input 1,-2
+output
+memory 109,-3,3,10,99
+"
+        );
+        assert_eq!(text.parse(), Ok(saved.clone()));
+
+        let path = std::env::temp_dir().join("intcode_snapshot_test.txt");
+        saved.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), saved);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!("intcode snapshot v1\nip x".parse::<Snapshot>().is_err());
+    }
+
+    #[test]
+    fn reverse_execution() {
+        // adds each input to a running total at [13] and prints it
+        let tape = vec![3, 12, 1, 12, 13, 13, 4, 13, 1105, 1, 0, 99, 0, 0];
+        let mut vm = Vm::new(&tape);
+        vm.record_history(true);
+        vm.input_all(vec![5, 7, 100]);
+        assert_eq!(vm.run(), Ok(State::Output(5)));
+        assert_eq!(vm.run(), Ok(State::Output(12)));
+        let after_two = vm.snapshot();
+        assert_eq!(vm.run(), Ok(State::Output(112)));
+        assert_eq!(vm.run(), Ok(State::NeedInput));
+
+        // back to the out that printed 112, then before the one that printed 12
+        assert_eq!(vm.reverse_to_output(), Some(112));
+        assert_eq!(vm.ip(), 6);
+        assert_eq!(vm.peek(13), 112);
+        assert!(vm.step_back());
+        assert_eq!(vm.peek(13), 12);
+        assert!(vm.rewind_to_write(12));
+        assert_eq!(vm.pending_input(), &VecDeque::from(vec![100]));
+        assert_eq!(vm.reverse_to_output(), Some(12));
+        vm.step().unwrap();
+        assert_eq!(vm.snapshot(), after_two);
+
+        assert!(!vm.rewind_to_write(999));
+        while vm.step_back() {}
+        let mut fresh = Vm::new(&tape);
+        fresh.input_all(vec![5, 7, 100]);
+        assert_eq!(vm.snapshot(), fresh.snapshot());
+    }
+
+    #[test]
+    fn memory_models() {
+        // writes 1 to [rb+1] with rb a billion, then prints it
+        let tape = vec![109, 1_000_000_000, 21101, 1, 0, 1, 204, 1, 99];
+        assert_eq!(
+            Vm::new(&tape).run(),
+            Err(VmError::AddressLimit {
+                ip: 2,
+                address: 1_000_000_001,
+                limit: DEFAULT_ADDRESS_LIMIT
+            })
+        );
+
+        let mut vm = Vm::with_memory(Memory::paged(&tape));
+        vm.set_address_limit(None);
+        assert_eq!(vm.run(), Ok(State::Output(1)));
+        assert_eq!(vm.memory().len(), 1_000_000_002);
+
+        let saved = vm.snapshot();
+        assert_eq!(saved.to_string().parse(), Ok(saved));
+
+        let mut vm = Vm::new(&[1101, 1, 1, 50, 99]);
+        vm.set_address_limit(Some(10));
+        assert_eq!(
+            vm.poke(10, 1),
+            Err(VmError::AddressLimit {
+                ip: 0,
+                address: 10,
+                limit: 10
+            })
+        );
+        assert_eq!(
+            vm.poke(usize::MAX, 1),
+            Err(VmError::AddressLimit {
+                ip: 0,
+                address: i64::MAX,
+                limit: i64::MAX as usize
+            })
+        );
+        assert_eq!(vm.memory().len(), 5);
+        assert_eq!(
+            vm.run(),
+            Err(VmError::AddressLimit {
+                ip: 0,
+                address: 50,
+                limit: 10
+            })
+        );
+    }
+
+    #[test]
+    fn self_modifying() {
+        // overwrites its own output instruction's operand, then jumps back to run it again
+        let tape = vec![104, 1, 1101, 0, 2, 1, 1106, 0, 0];
+        let mut vm = Vm::new(&tape);
+        vm.set_step_limit(Some(8));
+        assert_eq!(vm.run(), Ok(State::Output(1)));
+        assert_eq!(vm.run(), Ok(State::Output(2)));
+
+        // patching the add from outside has to be noticed too
+        vm.poke(4, 7).unwrap();
+        assert_eq!(vm.run(), Ok(State::Output(7)));
+
+        let mut uncached = Vm::new(&tape);
+        uncached.set_decode_cache(false);
+        uncached.set_step_limit(Some(8));
+        assert_eq!(uncached.run(), Ok(State::Output(1)));
+        assert_eq!(uncached.run(), Ok(State::Output(2)));
+    }
+
+    #[test]
+    fn cell_types() {
+        use num::BigInt;
+
+        // squares the last cell twice, then prints it
+        fn square_twice<C: From<i32>>(x: C) -> Vec<C> {
+            let mut tape: Vec<C> = vec![2, 11, 11, 11, 2, 11, 11, 11, 4, 11, 99]
+                .into_iter()
+                .map(C::from)
+                .collect();
+            tape.push(x);
+            tape
+        }
+        let wide: Vec<i128> = square_twice(1 << 20);
+        let mut vm = Vm::with_memory(Memory::flat(&wide));
+        assert_eq!(vm.run(), Ok(State::Output(1 << 80)));
+        assert_eq!(compile::compare_backends(&wide, &[]), Ok(vec![1 << 80]));
+
+        let big = square_twice(BigInt::from(1i64 << 40));
+        let mut vm = Vm::with_memory(Memory::paged(&big));
+        assert_eq!(vm.run(), Ok(State::Output(BigInt::from(1) << 160)));
+        let saved = vm.snapshot();
+        assert_eq!(saved.to_string().parse(), Ok(saved));
+
+        // plain i64s wrap around to 0 unless overflow is checked
+        let mut vm = Vm::new(&square_twice(1i64 << 20));
+        assert_eq!(vm.run(), Ok(State::Output(0)));
+        let mut vm = Vm::new(&square_twice(1i64 << 20));
+        vm.set_overflow(Overflow::Fail);
+        assert_eq!(vm.run(), Err(VmError::Overflow { ip: 4 }));
+
+        let narrow: Vec<i32> = square_twice(1 << 20);
+        let mut vm = Vm::with_memory(Memory::flat(&narrow));
+        vm.set_overflow(Overflow::Fail);
+        vm.set_backend(Backend::Compiled);
+        assert_eq!(vm.run(), Err(VmError::Overflow { ip: 0 }));
+    }
+
+    #[test]
+    fn step_limit() {
+        // jumps back to itself forever
+        let mut vm = Vm::new(&[1105, 1, 0]);
+        vm.set_step_limit(Some(1000));
+        assert_eq!(
+            vm.run(),
+            Err(VmError::StepLimitExceeded { ip: 0, limit: 1000 })
+        );
+        assert_eq!(vm.steps(), 1000);
+    }
+}
@@ -0,0 +1,158 @@
+use super::{State, Vm, VmError};
+use std::io::{self, BufRead, Write};
+
+/// Everything a program printed before it stopped to wait for input, or halted. Outputs
+/// that are ASCII make up `text`; anything else is usually an answer, and goes in `values`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Screen {
+    pub text: String,
+    pub values: Vec<i64>,
+    pub halted: bool,
+}
+
+impl Screen {
+    /// The first non-ASCII output, which is where most puzzles put their answer
+    pub fn value(&self) -> Option<i64> {
+        self.values.first().copied()
+    }
+}
+
+fn is_ascii(value: i64) -> bool {
+    (0..128).contains(&value)
+}
+
+/// A machine that talks in lines of text, like the ones on days 17, 21 and 25
+pub struct AsciiMachine {
+    vm: Vm,
+}
+
+impl AsciiMachine {
+    pub fn new(vm: Vm) -> AsciiMachine {
+        AsciiMachine { vm }
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    pub fn into_vm(self) -> Vm {
+        self.vm
+    }
+
+    /// Queues up `text` exactly as it is
+    pub fn send(&mut self, text: &str) {
+        self.vm.input_all(text.bytes().map(i64::from));
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.send(line);
+        self.vm.input(i64::from(b'\n'));
+    }
+
+    /// Runs until the program wants input that hasn't been sent yet, or halts
+    pub fn read_until_prompt(&mut self) -> Result<Screen, VmError> {
+        let mut screen = Screen::default();
+        loop {
+            match self.vm.run()? {
+                State::Output(out) if is_ascii(out) => screen.text.push(out as u8 as char),
+                State::Output(out) => screen.values.push(out),
+                State::NeedInput => break,
+                State::Halted => {
+                    screen.halted = true;
+                    break;
+                }
+            }
+        }
+        Ok(screen)
+    }
+
+    /// Plays the program by hand, printing whatever it says to `output` and sending it each
+    /// line read from `input`, until it halts or `input` runs out. Values that aren't
+    /// ASCII are shown in brackets.
+    pub fn play<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            let state = self.vm.run().map_err(io::Error::other)?;
+            match state {
+                State::Output(out) if is_ascii(out) => write!(output, "{}", out as u8 as char)?,
+                State::Output(out) => writeln!(output, "[{}]", out)?,
+                State::NeedInput => {
+                    output.flush()?;
+                    match lines.next() {
+                        Some(line) => self.send_line(line?.trim_end()),
+                        None => break,
+                    }
+                }
+                State::Halted => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::asm::assemble;
+    use super::*;
+
+    // echoes each line back, then prints its length plus 1000 as a number. An empty line
+    // makes it halt.
+    fn echo() -> AsciiMachine {
+        let tape = assemble(
+            r#"
+    prompt: out #62
+            out #10
+    read:   in [c]
+            eq [c], #10, [done]
+            jnz [done], end
+            add [len], #1, [len]
+            out [c]
+            jz #0, read
+    end:    out #10
+            eq [len], #0, [done]
+            jnz [done], quit
+            add [len], #1000, [len]
+            out [len]
+            add #0, #0, [len]
+            jz #0, prompt
+    quit:   hlt
+    c:      data 0
+    len:    data 0
+    done:   data 0
+            "#,
+        )
+        .unwrap();
+        AsciiMachine::new(Vm::new(&tape))
+    }
+
+    #[test]
+    fn lines() {
+        let mut machine = echo();
+        assert_eq!(machine.read_until_prompt().unwrap().text, ">\n");
+
+        machine.send_line("hello");
+        let screen = machine.read_until_prompt().unwrap();
+        assert_eq!(screen.text, "hello\n>\n");
+        assert_eq!(screen.value(), Some(1005));
+        assert!(!screen.halted);
+
+        machine.send_line("");
+        let screen = machine.read_until_prompt().unwrap();
+        assert_eq!(screen.text, "\n");
+        assert!(screen.halted);
+    }
+
+    #[test]
+    fn play() {
+        let mut output = Vec::new();
+        echo().play(&b"hi\nthere\n\n"[..], &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">\nhi\n[1002]\n>\nthere\n[1005]\n>\n\n"
+        );
+    }
+}
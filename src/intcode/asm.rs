@@ -0,0 +1,353 @@
+use super::{Instruction, Mode, Opcode};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Something wrong with the source, along with the (1-based) line it was on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// A number, or a label plus an offset that can only be resolved once every label is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Label(String, i64),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Code(Opcode, Vec<(Mode, Expr)>),
+    Data(Vec<Expr>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Code(opcode, _) => opcode.param_count() + 1,
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn parse_expr(s: &str) -> Result<Expr, String> {
+    let s = s.trim();
+    if let Ok(n) = s.parse() {
+        return Ok(Expr::Number(n));
+    }
+
+    // label, label+N or label-N
+    let (label, offset) = match s.find(['+', '-']) {
+        Some(split) => {
+            let offset = s[split..].replace(' ', "");
+            let offset = offset
+                .trim_start_matches('+')
+                .parse()
+                .map_err(|_| format!("bad offset in {:?}", s))?;
+            (s[..split].trim(), offset)
+        }
+        None => (s, 0),
+    };
+
+    if is_label(label) {
+        Ok(Expr::Label(label.to_owned(), offset))
+    } else {
+        Err(format!("expected a number or a label, found {:?}", s))
+    }
+}
+
+fn parse_operand(s: &str) -> Result<(Mode, Expr), String> {
+    let s = s.trim();
+    if let Some(value) = s.strip_prefix('#') {
+        Ok((Mode::Immediate, parse_expr(value)?))
+    } else if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim();
+        if inner == "rb" {
+            Ok((Mode::Relative, Expr::Number(0)))
+        } else if let Some(offset) = inner.strip_prefix("rb") {
+            let offset = offset.trim();
+            match offset.strip_prefix('+') {
+                Some(rest) => Ok((Mode::Relative, parse_expr(rest)?)),
+                None if offset.starts_with('-') => Ok((Mode::Relative, parse_expr(offset)?)),
+                None => Ok((Mode::Position, parse_expr(inner)?)),
+            }
+        } else {
+            Ok((Mode::Position, parse_expr(inner)?))
+        }
+    } else {
+        Ok((Mode::Immediate, parse_expr(s)?))
+    }
+}
+
+fn parse_string(s: &str) -> Result<Vec<Expr>, String> {
+    let mut values = Vec::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('"') => '"',
+                Some('\\') => '\\',
+                e => return Err(format!("unknown escape {:?}", e)),
+            }
+        } else {
+            c
+        };
+        values.push(Expr::Number(c as i64));
+    }
+    Ok(values)
+}
+
+/// Splits on commas and strips a trailing comment, ignoring both inside string literals
+fn split_operands(s: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in s.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                parts.push(String::new());
+                continue;
+            }
+            ';' if !in_string => break,
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    parts.iter().map(|p| p.trim().to_owned()).collect()
+}
+
+fn parse_statement(s: &str) -> Result<Statement, String> {
+    let (mnemonic, rest) = match s.find(char::is_whitespace) {
+        Some(split) => (&s[..split], &s[split..]),
+        None => (s, ""),
+    };
+    let operands = if rest.trim().is_empty() {
+        Vec::new()
+    } else {
+        split_operands(rest)
+    };
+
+    if mnemonic == "data" {
+        let mut values = Vec::new();
+        for operand in operands {
+            if operand.len() >= 2 && operand.starts_with('"') && operand.ends_with('"') {
+                values.extend(parse_string(&operand)?);
+            } else {
+                values.push(parse_expr(&operand)?);
+            }
+        }
+        return Ok(Statement::Data(values));
+    }
+
+    let opcode = Opcode::from_mnemonic(mnemonic)
+        .ok_or_else(|| format!("unknown mnemonic {:?}", mnemonic))?;
+    if operands.len() != opcode.param_count() {
+        return Err(format!(
+            "{} takes {} operands, found {}",
+            mnemonic,
+            opcode.param_count(),
+            operands.len()
+        ));
+    }
+
+    let operands = operands
+        .iter()
+        .map(|o| parse_operand(o))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(w) = opcode.write_param() {
+        if operands[w].0 == Mode::Immediate {
+            return Err(format!("{} can't write to an immediate", mnemonic));
+        }
+    }
+
+    Ok(Statement::Code(opcode, operands))
+}
+
+/// Turns assembly source into a tape. Accepts everything `disasm::listing` produces, so
+/// the address column of a listing is checked rather than treated as a label.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let error = |message| AsmError {
+            line: line_no,
+            message,
+        };
+
+        let mut line = line.trim();
+        // peel off any labels and listing addresses in front of the statement
+        while let Some(colon) = line.find(':') {
+            let name = line[..colon].trim();
+            if let Ok(expected) = name.parse::<usize>() {
+                if expected != address {
+                    return Err(error(format!(
+                        "listing says address {}, but this is {}",
+                        expected, address
+                    )));
+                }
+            } else if is_label(name) {
+                if labels.insert(name.to_owned(), address).is_some() {
+                    return Err(error(format!("label {:?} defined twice", name)));
+                }
+            } else {
+                break;
+            }
+            line = line[colon + 1..].trim();
+        }
+
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let statement = parse_statement(line).map_err(error)?;
+        address += statement.size();
+        statements.push((line_no, statement));
+    }
+
+    let resolve = |line: usize, expr: &Expr| match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Label(label, offset) => match labels.get(label) {
+            Some(&address) => Ok(address as i64 + offset),
+            None => Err(AsmError {
+                line,
+                message: format!("unknown label {:?}", label),
+            }),
+        },
+    };
+
+    let mut tape = Vec::with_capacity(address);
+    for (line, statement) in &statements {
+        match statement {
+            Statement::Code(opcode, operands) => {
+                let mut modes = [Mode::Position; 3];
+                for (mode, (operand_mode, _)) in modes.iter_mut().zip(operands) {
+                    *mode = *operand_mode;
+                }
+                let instr = Instruction {
+                    opcode: *opcode,
+                    modes,
+                };
+                tape.push(instr.encode());
+                for (_, expr) in operands {
+                    tape.push(resolve(*line, expr)?);
+                }
+            }
+            Statement::Data(values) => {
+                for expr in values {
+                    tape.push(resolve(*line, expr)?);
+                }
+            }
+        }
+    }
+
+    Ok(tape)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::disasm::listing;
+    use super::super::Vm;
+    use super::*;
+
+    #[test]
+    fn quine() {
+        let tape = assemble(
+            "
+    start:  arb #1
+            out [rb-1]
+            add [100], #1, [100]
+            eq [100], #16, [101]
+            jz [101], start
+            hlt
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            tape,
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
+        );
+    }
+
+    #[test]
+    fn labels_and_data() {
+        let tape = assemble(
+            r#"
+            ; prints a message one character at a time
+    start:  out [msg+0]             ; patched to walk along the string
+            add [start+1], #1, [start+1]
+            lt [start+1], #end, [more]
+            jnz [more], start
+            hlt
+    more:   data 0
+    msg:    data "Hi,\n", 7
+    end:
+            "#,
+        )
+        .unwrap();
+        let output = Vm::new(&tape).run_to_halt().unwrap();
+        assert_eq!(output, vec![72, 105, 44, 10, 7]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let tape = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99, 72, 101, 108, 108, 111, 10,
+        ];
+        assert_eq!(assemble(&listing(&tape)), Ok(tape));
+
+        // the VM would run 1104 as `out #7`, but it has a mode for a parameter `out` doesn't
+        // have, so it has to be listed as data to come back the same
+        let tape = vec![1104, 7, 99];
+        assert_eq!(assemble(&listing(&tape)), Ok(tape));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            assemble("add #1, #2").unwrap_err().message,
+            "add takes 3 operands, found 2"
+        );
+        assert_eq!(
+            assemble("\n in #5").unwrap_err(),
+            AsmError {
+                line: 2,
+                message: "in can't write to an immediate".to_owned()
+            }
+        );
+        assert_eq!(
+            assemble("jnz #1, nowhere").unwrap_err().message,
+            "unknown label \"nowhere\""
+        );
+        assert!(assemble("0003: hlt").is_err());
+    }
+}
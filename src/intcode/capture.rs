@@ -0,0 +1,161 @@
+use super::network::{Captured, Packet};
+use std::io::{self, Write};
+
+/// The pcap link type for Ethernet, so that every packet gets wrapped in made-up Ethernet,
+/// IPv4 and UDP headers that Wireshark and tcpdump can read without any help
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+/// The UDP port on both ends of every packet
+pub const PORT: u16 = 2019;
+
+/// Computer `n` is at 10.0.0.n, or more generally `10.` followed by the low 24 bits of its
+/// address, so the NAT is 10.0.0.255
+pub fn ip_address(node: i64) -> [u8; 4] {
+    let [_, a, b, c] = (node as u32).to_be_bytes();
+    [10, a, b, c]
+}
+
+fn mac_address(node: i64) -> [u8; 6] {
+    let [_, a, b, c] = ip_address(node);
+    // locally administered, so it can't clash with a real card
+    [0x02, 0, 10, a, b, c]
+}
+
+/// The whole Ethernet frame for a packet, with `x` and `y` as big-endian 64-bit integers in
+/// the UDP payload
+fn frame(packet: &Packet) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(16);
+    payload.extend_from_slice(&packet.x.to_be_bytes());
+    payload.extend_from_slice(&packet.y.to_be_bytes());
+    let udp_len = 8 + payload.len() as u16;
+
+    let mut ip = Vec::with_capacity(20);
+    ip.extend_from_slice(&[0x45, 0]); // version 4, 20 byte header
+    ip.extend_from_slice(&(20 + udp_len).to_be_bytes());
+    ip.extend_from_slice(&[0, 0, 0x40, 0]); // no id, don't fragment
+    ip.extend_from_slice(&[64, 17, 0, 0]); // TTL, UDP, checksum to fill in
+    ip.extend_from_slice(&ip_address(packet.from));
+    ip.extend_from_slice(&ip_address(packet.to));
+    let checksum = ipv4_checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut frame = Vec::with_capacity(14 + ip.len() + udp_len as usize);
+    frame.extend_from_slice(&mac_address(packet.to));
+    frame.extend_from_slice(&mac_address(packet.from));
+    frame.extend_from_slice(&0x0800u16.to_be_bytes());
+    frame.extend_from_slice(&ip);
+    frame.extend_from_slice(&PORT.to_be_bytes());
+    frame.extend_from_slice(&PORT.to_be_bytes());
+    frame.extend_from_slice(&udp_len.to_be_bytes());
+    frame.extend_from_slice(&[0, 0]); // UDP checksums are optional over IPv4
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Writes the log as CSV, one packet per line under a `step,from,to,x,y` header
+pub fn write_csv<W: Write>(log: &[Captured], mut out: W) -> io::Result<()> {
+    writeln!(out, "step,from,to,x,y")?;
+    for captured in log {
+        let p = captured.packet;
+        writeln!(out, "{},{},{},{},{}", captured.step, p.from, p.to, p.x, p.y)?;
+    }
+    Ok(())
+}
+
+/// Writes the log as a pcap file. Each packet's step becomes its timestamp, one
+/// microsecond per instruction, and it's sent over UDP from `ip_address(from)` to
+/// `ip_address(to)`.
+pub fn write_pcap<W: Write>(log: &[Captured], mut out: W) -> io::Result<()> {
+    // magic number, version 2.4, UTC, no timestamp accuracy, and the snapshot length
+    out.write_all(&0xa1b2_c3d4u32.to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&4u16.to_le_bytes())?;
+    out.write_all(&0i32.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&65535u32.to_le_bytes())?;
+    out.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+
+    for captured in log {
+        let data = frame(&captured.packet);
+
+        let seconds = (captured.step / 1_000_000) as u32;
+        let micros = (captured.step % 1_000_000) as u32;
+        out.write_all(&seconds.to_le_bytes())?;
+        out.write_all(&micros.to_le_bytes())?;
+        out.write_all(&(data.len() as u32).to_le_bytes())?;
+        out.write_all(&(data.len() as u32).to_le_bytes())?;
+        out.write_all(&data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> Vec<Captured> {
+        let packet = |from, to, x, y| Packet { from, to, x, y };
+        vec![
+            Captured {
+                step: 12,
+                packet: packet(0, 255, 3, -4),
+            },
+            Captured {
+                step: 2_000_005,
+                packet: packet(255, 0, 3, -4),
+            },
+        ]
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        write_csv(&log(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "step,from,to,x,y\n12,0,255,3,-4\n2000005,255,0,3,-4\n"
+        );
+    }
+
+    #[test]
+    fn pcap() {
+        let mut out = Vec::new();
+        write_pcap(&log(), &mut out).unwrap();
+        // a 24 byte header, then a 16 byte record header and a 58 byte frame per packet
+        assert_eq!(out.len(), 24 + 2 * (16 + 58));
+        assert_eq!(&out[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&out[20..24], &[1, 0, 0, 0]);
+
+        let second = &out[24 + 16 + 58..];
+        assert_eq!(&second[..8], &[2, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(&second[8..16], &[58, 0, 0, 0, 58, 0, 0, 0]);
+
+        let frame = &second[16..];
+        assert_eq!(&frame[..6], &[2, 0, 10, 0, 0, 0]);
+        assert_eq!(&frame[6..12], &[2, 0, 10, 0, 0, 255]);
+        assert_eq!(&frame[12..14], &[0x08, 0x00]);
+
+        let ip = &frame[14..34];
+        assert_eq!(&ip[..4], &[0x45, 0, 0, 44]);
+        assert_eq!(ip[9], 17);
+        assert_eq!(&ip[12..16], &[10, 0, 0, 255]);
+        assert_eq!(&ip[16..20], &[10, 0, 0, 0]);
+        assert_eq!(ipv4_checksum(ip), 0);
+
+        let udp = &frame[34..];
+        assert_eq!(&udp[..8], &[0x07, 0xe3, 0x07, 0xe3, 0, 24, 0, 0]);
+        assert_eq!(&udp[8..16], &3i64.to_be_bytes());
+        assert_eq!(&udp[16..24], &(-4i64).to_be_bytes());
+    }
+}
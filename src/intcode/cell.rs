@@ -0,0 +1,103 @@
+use num::{BigInt, ToPrimitive, Zero};
+use std::fmt;
+use std::str::FromStr;
+
+/// Something that can live in a cell of Intcode memory. Implemented for `i32`, `i64`,
+/// `i128` and `BigInt`.
+pub trait Cell: Clone + Ord + fmt::Debug + fmt::Display + FromStr + Send + Sync + 'static {
+    fn zero() -> Self;
+    fn one() -> Self;
+    /// Turns an address into a cell. Narrower types truncate, but only tracing ever needs
+    /// to do this.
+    fn from_i64(value: i64) -> Self;
+    /// `None` if the value doesn't fit in an `i64`
+    fn to_i64(&self) -> Option<i64>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+
+    /// Like `to_i64`, but clamps values that are too big, for putting them in errors
+    fn saturating_i64(&self) -> i64 {
+        match self.to_i64() {
+            Some(value) => value,
+            None if *self < Self::zero() => i64::MIN,
+            None => i64::MAX,
+        }
+    }
+}
+
+macro_rules! primitive_cell {
+    ($($t:ty),*) => {$(
+        impl Cell for $t {
+            fn zero() -> $t {
+                0
+            }
+
+            fn one() -> $t {
+                1
+            }
+
+            fn from_i64(value: i64) -> $t {
+                value as $t
+            }
+
+            fn to_i64(&self) -> Option<i64> {
+                ToPrimitive::to_i64(self)
+            }
+
+            fn checked_add(&self, other: &$t) -> Option<$t> {
+                <$t>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, other: &$t) -> Option<$t> {
+                <$t>::checked_mul(*self, *other)
+            }
+
+            fn wrapping_add(&self, other: &$t) -> $t {
+                <$t>::wrapping_add(*self, *other)
+            }
+
+            fn wrapping_mul(&self, other: &$t) -> $t {
+                <$t>::wrapping_mul(*self, *other)
+            }
+        }
+    )*};
+}
+
+primitive_cell!(i32, i64, i128);
+
+/// Never overflows, so wrapping and checked arithmetic are the same thing
+impl Cell for BigInt {
+    fn zero() -> BigInt {
+        Zero::zero()
+    }
+
+    fn one() -> BigInt {
+        BigInt::from(1)
+    }
+
+    fn from_i64(value: i64) -> BigInt {
+        BigInt::from(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &BigInt) -> BigInt {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &BigInt) -> BigInt {
+        self * other
+    }
+}
@@ -0,0 +1,744 @@
+use super::{Cell, Memory, Mode, Opcode, State, Vm, VmError};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// How `Vm::run` executes a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Decodes and executes one instruction at a time. Always used by `Vm::step`.
+    Interpreter,
+    /// Translates straight-line runs of code into blocks of pre-decoded operations the first
+    /// time they're reached, and executes those. Falls back to the interpreter for anything
+    /// it can't translate, for code that has been written to, and whenever a trace hook,
+    /// watchpoints or history are switched on.
+    Compiled,
+}
+
+/// A parameter with its mode baked in. Position addresses are checked when the block is
+/// built, but relative ones depend on the relative base so they're checked as they run.
+#[derive(Debug, Clone)]
+enum Arg<C> {
+    Immediate(C),
+    Position(usize),
+    Relative(i64),
+}
+
+#[derive(Debug, Clone)]
+enum Op<C> {
+    Add(Arg<C>, Arg<C>, Arg<C>),
+    Mul(Arg<C>, Arg<C>, Arg<C>),
+    In(Arg<C>),
+    Out(Arg<C>),
+    Jnz(Arg<C>, Arg<C>),
+    Jz(Arg<C>, Arg<C>),
+    Lt(Arg<C>, Arg<C>, Arg<C>),
+    Eq(Arg<C>, Arg<C>, Arg<C>),
+    Arb(Arg<C>),
+    Hlt,
+}
+
+/// A run of instructions that's always entered at the top. It ends at a jump or a halt, or
+/// just before something that can't be compiled.
+#[derive(Debug)]
+struct Block<C> {
+    start: usize,
+    /// The memory the block was built from, to check it against after a `restore`
+    source: Vec<C>,
+    /// Each operation along with its address
+    ops: Vec<(usize, Op<C>)>,
+}
+
+/// Every block built so far, keyed by the address it starts at
+#[derive(Debug, Clone)]
+pub(crate) struct Blocks<C> {
+    blocks: HashMap<usize, (Arc<Block<C>>, u64)>,
+    /// How many blocks each cell of memory is part of
+    covered: Vec<u32>,
+    /// Cells of code the program has written to. Nothing gets compiled over them again.
+    dirty: Vec<bool>,
+    /// Bumped whenever memory might have changed behind our back. Blocks from an older
+    /// generation have to be checked against memory before they're trusted.
+    generation: u64,
+}
+
+impl<C> Blocks<C> {
+    fn new() -> Blocks<C> {
+        Blocks {
+            blocks: HashMap::new(),
+            covered: Vec::new(),
+            dirty: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    /// Called after memory was replaced wholesale
+    pub(crate) fn memory_replaced(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Called for every write. Throws away any block the address is part of, returning
+    /// whether there were any.
+    pub(crate) fn invalidate(&mut self, address: usize) -> bool {
+        if self.covered.get(address).copied().unwrap_or(0) == 0 {
+            return false;
+        }
+
+        let stale: Vec<_> = self
+            .blocks
+            .values()
+            .filter(|(block, _)| (block.start..block.start + block.source.len()).contains(&address))
+            .map(|(block, _)| Arc::clone(block))
+            .collect();
+        for block in stale {
+            self.remove(&block);
+        }
+
+        if address >= self.dirty.len() {
+            self.dirty.resize(address + 1, false);
+        }
+        self.dirty[address] = true;
+        true
+    }
+
+    fn remove(&mut self, block: &Block<C>) {
+        self.blocks.remove(&block.start);
+        for count in &mut self.covered[block.start..block.start + block.source.len()] {
+            *count -= 1;
+        }
+    }
+
+    fn insert(&mut self, block: Block<C>) -> Arc<Block<C>> {
+        let end = block.start + block.source.len();
+        if end > self.covered.len() {
+            self.covered.resize(end, 0);
+        }
+        for count in &mut self.covered[block.start..end] {
+            *count += 1;
+        }
+        let block = Arc::new(block);
+        self.blocks
+            .insert(block.start, (Arc::clone(&block), self.generation));
+        block
+    }
+
+    fn is_dirty(&self, address: usize) -> bool {
+        self.dirty.get(address).copied().unwrap_or(false)
+    }
+}
+
+impl<C: Cell> Vm<C> {
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.blocks = match backend {
+            Backend::Interpreter => None,
+            Backend::Compiled => Some(Blocks::new()),
+        };
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.blocks {
+            Some(_) => Backend::Compiled,
+            None => Backend::Interpreter,
+        }
+    }
+
+    /// Checks an address that's part of the program, so it can't depend on anything else
+    fn static_address(&self, value: &C) -> Option<usize> {
+        let value = value.to_i64()?;
+        if value < 0 {
+            return None;
+        }
+        match self.address_limit {
+            Some(limit) if value as usize >= limit => None,
+            _ => Some(value as usize),
+        }
+    }
+
+    fn build_block(&self, blocks: &Blocks<C>, start: usize) -> Option<Block<C>> {
+        let mut ops = Vec::new();
+        let mut address = start;
+
+        while let Ok(decoded) = self.decode_uncached(address) {
+            let instr = decoded.instr;
+            if (address..address + instr.size()).any(|a| blocks.is_dirty(a)) {
+                break;
+            }
+            if let Some(w) = instr.opcode.write_param() {
+                if instr.modes[w] == Mode::Immediate {
+                    break;
+                }
+            }
+
+            let mut args = [Arg::Position(0), Arg::Position(0), Arg::Position(0)];
+            let mut compiled = true;
+            for (idx, arg) in args.iter_mut().enumerate().take(instr.size() - 1) {
+                let value = &decoded.args[idx];
+                *arg = match instr.modes[idx] {
+                    Mode::Immediate => Arg::Immediate(value.clone()),
+                    Mode::Relative => match value.to_i64() {
+                        Some(offset) => Arg::Relative(offset),
+                        None => {
+                            compiled = false;
+                            break;
+                        }
+                    },
+                    Mode::Position => match self.static_address(value) {
+                        Some(checked) => Arg::Position(checked),
+                        None => {
+                            compiled = false;
+                            break;
+                        }
+                    },
+                };
+            }
+            if !compiled {
+                break;
+            }
+
+            let [a, b, c] = args;
+            let op = match instr.opcode {
+                Opcode::Add => Op::Add(a, b, c),
+                Opcode::Mul => Op::Mul(a, b, c),
+                Opcode::In => Op::In(a),
+                Opcode::Out => Op::Out(a),
+                Opcode::Jnz => Op::Jnz(a, b),
+                Opcode::Jz => Op::Jz(a, b),
+                Opcode::Lt => Op::Lt(a, b, c),
+                Opcode::Eq => Op::Eq(a, b, c),
+                Opcode::Arb => Op::Arb(a),
+                Opcode::Hlt => Op::Hlt,
+            };
+            ops.push((address, op));
+            address += instr.size();
+
+            match instr.opcode {
+                Opcode::Jnz | Opcode::Jz | Opcode::Hlt => break,
+                _ => {}
+            }
+        }
+
+        if ops.is_empty() {
+            return None;
+        }
+        Some(Block {
+            start,
+            source: (start..address).map(|a| self.read(a)).collect(),
+            ops,
+        })
+    }
+
+    /// The block starting at `ip`, building it if needed
+    fn block_at(&self, blocks: &mut Blocks<C>, ip: usize) -> Option<Arc<Block<C>>> {
+        if let Some((block, generation)) = blocks.blocks.get(&ip) {
+            if *generation == blocks.generation {
+                return Some(Arc::clone(block));
+            }
+            let block = Arc::clone(block);
+            let unchanged = block
+                .source
+                .iter()
+                .enumerate()
+                .all(|(offset, value)| self.read(ip + offset) == *value);
+            if unchanged {
+                blocks.blocks.get_mut(&ip).unwrap().1 = blocks.generation;
+                return Some(block);
+            }
+            blocks.remove(&block);
+        }
+
+        let block = self.build_block(blocks, ip)?;
+        Some(blocks.insert(block))
+    }
+
+    fn relative(&self, offset: i64) -> Result<usize, VmError> {
+        let address = self.relative_base.saturating_add(offset);
+        self.to_address(address)
+    }
+
+    fn value(&self, arg: &Arg<C>) -> Result<C, VmError> {
+        match arg {
+            Arg::Immediate(value) => Ok(value.clone()),
+            Arg::Position(address) => Ok(self.read(*address)),
+            Arg::Relative(offset) => Ok(self.read(self.relative(*offset)?)),
+        }
+    }
+
+    /// Stores a result, returning whether it landed on compiled code
+    fn put(&mut self, blocks: &mut Blocks<C>, arg: &Arg<C>, value: C) -> Result<bool, VmError> {
+        let address = match arg {
+            Arg::Immediate(_) => unreachable!("immediate writes aren't compiled"),
+            Arg::Position(address) => *address,
+            Arg::Relative(offset) => self.relative(*offset)?,
+        };
+        self.write(address, value);
+        Ok(blocks.invalidate(address))
+    }
+
+    /// Runs compiled blocks for as long as it can. Returns `None` when the next instruction
+    /// has to go through the interpreter instead.
+    pub(crate) fn run_compiled(&mut self) -> Result<Option<State<C>>, VmError> {
+        let observed =
+            self.trace_hook.is_some() || self.history.is_some() || !self.watchpoints.is_empty();
+        if observed {
+            return Ok(None);
+        }
+        let mut blocks = match self.blocks.take() {
+            Some(blocks) => blocks,
+            None => return Ok(None),
+        };
+        let result = self.run_blocks(&mut blocks);
+        self.blocks = Some(blocks);
+        result
+    }
+
+    fn run_blocks(&mut self, blocks: &mut Blocks<C>) -> Result<Option<State<C>>, VmError> {
+        'blocks: loop {
+            let block = match self.block_at(blocks, self.ip) {
+                Some(block) => block,
+                None => return Ok(None),
+            };
+
+            for (ip, op) in &block.ops {
+                let ip = *ip;
+                if let Some(limit) = self.step_limit {
+                    if self.steps >= limit {
+                        return Ok(None);
+                    }
+                }
+                self.ip = ip;
+
+                let (next, wrote_code) = match op {
+                    Op::Add(a, b, c) | Op::Mul(a, b, c) => {
+                        let opcode = match op {
+                            Op::Add(..) => Opcode::Add,
+                            _ => Opcode::Mul,
+                        };
+                        let value = self.arithmetic(opcode, &self.value(a)?, &self.value(b)?)?;
+                        (ip + 4, self.put(blocks, c, value)?)
+                    }
+                    Op::Lt(a, b, c) | Op::Eq(a, b, c) => {
+                        let result = match op {
+                            Op::Lt(..) => self.value(a)? < self.value(b)?,
+                            _ => self.value(a)? == self.value(b)?,
+                        };
+                        let value = if result { C::one() } else { C::zero() };
+                        (ip + 4, self.put(blocks, c, value)?)
+                    }
+                    Op::In(a) => {
+                        let input = match self.input.pop_front() {
+                            Some(input) => input,
+                            None => return Ok(Some(State::NeedInput)),
+                        };
+                        match self.put(blocks, a, input.clone()) {
+                            Ok(wrote_code) => (ip + 2, wrote_code),
+                            Err(e) => {
+                                self.input.push_front(input);
+                                return Err(e);
+                            }
+                        }
+                    }
+                    Op::Out(a) => {
+                        let out = self.value(a)?;
+                        self.ip = ip + 2;
+                        self.steps += 1;
+                        return Ok(Some(State::Output(out)));
+                    }
+                    Op::Jnz(a, b) | Op::Jz(a, b) => {
+                        let jump = match op {
+                            Op::Jnz(..) => self.value(a)? != C::zero(),
+                            _ => self.value(a)? == C::zero(),
+                        };
+                        if jump {
+                            let target = self.value(b)?;
+                            self.ip = self.to_address(target.saturating_i64())?;
+                        } else {
+                            self.ip = ip + 3;
+                        }
+                        self.steps += 1;
+                        continue 'blocks;
+                    }
+                    Op::Arb(a) => {
+                        let by = self.value(a)?;
+                        self.adjust_relative_base(&by)?;
+                        (ip + 2, false)
+                    }
+                    Op::Hlt => {
+                        self.steps += 1;
+                        return Ok(Some(State::Halted));
+                    }
+                };
+
+                self.ip = next;
+                self.steps += 1;
+                if wrote_code {
+                    // the rest of this block might not exist any more
+                    continue 'blocks;
+                }
+            }
+        }
+    }
+}
+
+/// Runs `tape` on both backends with the same input, checking that every output, the way
+/// it stopped, and the final state of the machine all match. Returns the outputs.
+pub fn compare_backends<C: Cell>(tape: &[C], input: &[C]) -> Result<Vec<C>, String> {
+    let run = |backend| {
+        let mut vm = Vm::with_memory(Memory::flat(tape));
+        vm.set_backend(backend);
+        vm.input_all(input.iter().cloned());
+        let mut outputs = Vec::new();
+        let stop = loop {
+            match vm.run() {
+                Ok(State::Output(out)) => outputs.push(out),
+                stop => break stop,
+            }
+        };
+        (outputs, stop, vm.snapshot())
+    };
+
+    let (outputs, stop, snapshot) = run(Backend::Interpreter);
+    let (compiled_outputs, compiled_stop, compiled_snapshot) = run(Backend::Compiled);
+
+    if compiled_outputs != outputs {
+        Err(format!(
+            "outputs differ: interpreter {:?}, compiled {:?}",
+            outputs, compiled_outputs
+        ))
+    } else if compiled_stop != stop {
+        Err(format!(
+            "stopped differently: interpreter {:?}, compiled {:?}",
+            stop, compiled_stop
+        ))
+    } else if compiled_snapshot != snapshot {
+        Err(format!(
+            "ended in different states: interpreter at {}, compiled at {}",
+            snapshot.ip(),
+            compiled_snapshot.ip()
+        ))
+    } else {
+        Ok(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::asm::assemble;
+    use super::*;
+
+    #[test]
+    fn day_examples() {
+        let compare_to_eight = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for input in 7..=9 {
+            assert!(compare_backends(&compare_to_eight, &[input]).is_ok());
+        }
+
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(compare_backends(&quine, &[]), Ok(quine.clone()));
+
+        // day 2's example, and day 7's feedback amplifier waiting on its next signal
+        assert!(compare_backends(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]).is_ok());
+        let amplifier = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        assert_eq!(compare_backends(&amplifier, &[9, 0]), Ok(vec![5]));
+    }
+
+    #[test]
+    fn self_modifying() {
+        // the loop counter lives in the immediate operand of the first instruction
+        let tape = assemble(
+            "
+    loop:   add #0, #1, [loop+1]
+            out [loop+1]
+            lt [loop+1], #5, [more]
+            jnz [more], loop
+            hlt
+    more:   data 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(compare_backends(&tape, &[]), Ok(vec![1, 2, 3, 4, 5]));
+
+        // an input written straight into the next instruction
+        let tape = vec![3, 3, 104, 0, 99];
+        assert_eq!(compare_backends(&tape, &[42]), Ok(vec![42]));
+    }
+
+    #[test]
+    fn faults_and_limits() {
+        assert!(compare_backends(&[109, -5, 204, 0, 99], &[]).is_ok());
+        assert!(compare_backends(&[1101, 1, 1, 0, 42], &[]).is_ok());
+
+        let mut vm = Vm::new(&[1105, 1, 0]);
+        vm.set_backend(Backend::Compiled);
+        vm.set_step_limit(Some(100));
+        assert_eq!(
+            vm.run(),
+            Err(VmError::StepLimitExceeded { ip: 0, limit: 100 })
+        );
+    }
+
+    #[test]
+    fn restored_blocks() {
+        let tape = vec![3, 9, 1002, 9, 3, 9, 4, 9, 99, 0];
+        let mut vm = Vm::new(&tape);
+        vm.set_backend(Backend::Compiled);
+        let start = vm.snapshot();
+        for input in 1..5 {
+            vm.restore(&start);
+            vm.input(input);
+            assert_eq!(vm.run(), Ok(State::Output(input * 3)));
+        }
+
+        // restoring different code has to be noticed
+        let mut patched = tape.clone();
+        patched[4] = 10;
+        vm.restore(&Vm::new(&patched).snapshot());
+        vm.input(2);
+        assert_eq!(vm.run(), Ok(State::Output(20)));
+    }
+
+    fn both_backends(source: &str, input: &[i64]) -> Vec<i64> {
+        let tape = assemble(source).unwrap();
+        compare_backends(&tape, input).unwrap()
+    }
+
+    // The puzzle programs aren't in the repo, so these are small stand-ins that talk the
+    // same way the real ones for days 11 to 25 do
+
+    #[test]
+    fn day11_painter() {
+        // reads the colour underneath, then paints the opposite colour and turns
+        let source = "
+    loop:   in [colour]
+            mul [colour], #-1, [paint]
+            add [paint], #1, [paint]
+            out [paint]
+            out [turn]
+            eq [turn], #0, [turn]
+            add [steps], #1, [steps]
+            lt [steps], #4, [more]
+            jnz [more], loop
+            hlt
+    colour: data 0
+    paint:  data 0
+    turn:   data 0
+    steps:  data 0
+    more:   data 0
+            ";
+        assert_eq!(
+            both_backends(source, &[0, 1, 0, 0]),
+            vec![1, 0, 0, 1, 1, 0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn day13_arcade() {
+        // draws tiles as x, y, id triples with scratch space off the relative base, then
+        // the score
+        let source = "
+            arb #stack
+    row:    add #0, #0, [x]
+    col:    out [x]
+            out [y]
+            add [x], [y], [rb]
+            out [rb]
+            add [x], #1, [x]
+            lt [x], #3, [rb+1]
+            jnz [rb+1], col
+            add [y], #1, [y]
+            lt [y], #2, [rb+1]
+            jnz [rb+1], row
+            out #-1
+            out #0
+            mul #6, #7, [rb]
+            out [rb]
+            hlt
+    x:      data 0
+    y:      data 0
+    stack:  data 0, 0
+            ";
+        assert_eq!(
+            both_backends(source, &[]),
+            vec![0, 0, 0, 1, 0, 1, 2, 0, 2, 0, 1, 1, 1, 1, 2, 2, 1, 3, -1, 0, 42]
+        );
+    }
+
+    #[test]
+    fn day15_droid() {
+        // a corridor running east, with the oxygen system three steps along
+        let source = "
+    loop:   in [dir]
+            eq [dir], #4, [t]
+            jnz [t], east
+            eq [dir], #3, [t]
+            jnz [t], west
+    wall:   out #0
+            jz #0, loop
+    east:   add [pos], #1, [pos]
+            jz #0, moved
+    west:   lt [pos], #1, [t]
+            jnz [t], wall
+            add [pos], #-1, [pos]
+    moved:  eq [pos], #3, [t]
+            jnz [t], found
+            out #1
+            jz #0, loop
+    found:  out #2
+            jz #0, loop
+    dir:    data 0
+    pos:    data 0
+    t:      data 0
+            ";
+        assert_eq!(
+            both_backends(source, &[1, 3, 4, 4, 3, 4, 4, 4]),
+            vec![0, 0, 1, 1, 1, 1, 2, 1]
+        );
+    }
+
+    #[test]
+    fn day17_camera() {
+        // prints the scaffold by walking the relative base along it
+        let source = r#"
+            arb #map
+    print:  jz [rb], done
+            out [rb]
+            arb #1
+            jz #0, print
+    done:   hlt
+    map:    data ".#.\n###\n.^.\n", 0
+            "#;
+        let view: String = both_backends(source, &[])
+            .into_iter()
+            .map(|c| c as u8 as char)
+            .collect();
+        assert_eq!(view, ".#.\n###\n.^.\n");
+    }
+
+    #[test]
+    fn day19_beam() {
+        // one query per run, like the real drone program
+        let source = "
+            in [x]
+            in [y]
+            lt [y], [x], [t]
+            jnz [t], miss
+            mul [x], #2, [t]
+            lt [t], [y], [t]
+            jnz [t], miss
+            out #1
+            hlt
+    miss:   out #0
+            hlt
+    x:      data 0
+    y:      data 0
+    t:      data 0
+            ";
+        for &(x, y, pulled) in &[(3, 4, 1), (1, 4, 0), (5, 4, 0), (0, 0, 1)] {
+            assert_eq!(both_backends(source, &[x, y]), vec![pulled]);
+        }
+    }
+
+    #[test]
+    fn day21_springdroid() {
+        // reads springscript until the second line, then reports a non-ASCII answer
+        let source = "
+            out #62
+            out #10
+    read:   in [c]
+            eq [c], #10, [t]
+            jnz [t], line
+            add [sum], [c], [sum]
+            jz #0, read
+    line:   add [lines], #1, [lines]
+            lt [lines], #2, [t]
+            jnz [t], read
+            add [sum], #1000, [sum]
+            out [sum]
+            hlt
+    c:      data 0
+    t:      data 0
+    sum:    data 0
+    lines:  data 0
+            ";
+        let script = "NOT A J\nWALK\n";
+        let input: Vec<_> = script.bytes().map(i64::from).collect();
+        let sum: i64 = script.bytes().filter(|&b| b != b'\n').map(i64::from).sum();
+        assert_eq!(both_backends(source, &input), vec![62, 10, sum + 1000]);
+    }
+
+    #[test]
+    fn day23_node() {
+        // reads its address, then passes every packet on to the next address with x + 1
+        // and y * 2, polling with -1 in between
+        let source = "
+            in [addr]
+    poll:   in [x]
+            eq [x], #-1, [t]
+            jnz [t], poll
+            in [y]
+            add [addr], #1, [t]
+            out [t]
+            add [x], #1, [x]
+            out [x]
+            mul [y], #2, [y]
+            out [y]
+            jz #0, poll
+    addr:   data 0
+    x:      data 0
+    y:      data 0
+    t:      data 0
+            ";
+        assert_eq!(
+            both_backends(source, &[2, -1, -1, 5, 7, -1, 1, 1]),
+            vec![3, 6, 14, 3, 2, 2]
+        );
+    }
+
+    #[test]
+    fn day25_adventure() {
+        // a one-room adventure. Printing is a subroutine that patches its own operands to
+        // walk the string, and returns through a jump to an address kept in memory.
+        let source = r#"
+    start:  add #room, #0, [str]
+            add #back, #0, [ret]
+            jz #0, print
+    back:   in [c]
+            eq [c], #110, [t]
+            jnz [t], north
+    skip:   eq [c], #10, [t]
+            jnz [t], start
+            in [c]
+            jz #0, skip
+    north:  add #moved, #0, [str]
+            add #end, #0, [ret]
+            jz #0, print
+    end:    hlt
+    print:  add [str], #0, [test+1]
+            add [str], #0, [emit+1]
+    test:   jz [0], [ret]
+    emit:   out [0]
+            add [str], #1, [str]
+            jz #0, print
+    str:    data 0
+    ret:    data 0
+    c:      data 0
+    t:      data 0
+    room:   data "== Hull ==\nCommand?\n", 0
+    moved:  data "north\n", 0
+            "#;
+        let input: Vec<_> = "west\nnorth\n".bytes().map(i64::from).collect();
+        let text: String = both_backends(source, &input)
+            .into_iter()
+            .map(|c| c as u8 as char)
+            .collect();
+        let room = "== Hull ==\nCommand?\n";
+        assert_eq!(text, format!("{}{}north\n", room, room));
+    }
+}
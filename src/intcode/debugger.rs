@@ -0,0 +1,598 @@
+use super::disasm::{instruction_in, Item, Operand};
+use super::{Access, Mode, State, Vm, VmError, WatchHit};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use termion::color;
+use termion::input::TermRead;
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (default 1)
+c, continue          run until a breakpoint, input, or halt
+bs, back [n]         undo n instructions (default 1)
+rewind <addr>        go back to just before the last write to an address
+ro, rout             go back to just before the previous output
+b, break <addr>      set a breakpoint
+d, delete <addr>     remove a breakpoint
+bl, breakpoints      list breakpoints
+w, watch <addr> [r|w|rw]
+                     stop when an address is read and/or written (default rw)
+unwatch <addr>       remove a watchpoint
+x, mem <addr> [n]    show n cells of memory (default 8)
+poke <addr> <value>  change a cell of memory
+r, regs              show ip, relative base and pending input
+l, list [n]          disassemble n instructions from ip (default 5)
+i, input <values>    queue input, either numbers or a \"string\" sent as ASCII
+h, help              show this message
+q, quit              leave the debugger
+an empty line repeats the last command";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Back(usize),
+    Rewind(usize),
+    ReverseOutput,
+    Break(usize),
+    Delete(usize),
+    Breakpoints,
+    Watch(usize, Access),
+    Unwatch(usize),
+    Memory(usize, usize),
+    Poke(usize, i64),
+    Registers,
+    List(usize),
+    Input(Vec<i64>),
+    Help,
+    Quit,
+}
+
+fn arg<T: std::str::FromStr>(args: &[&str], idx: usize, default: Option<T>) -> Result<T, String> {
+    match args.get(idx) {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| format!("couldn't understand {:?}", arg)),
+        None => default.ok_or_else(|| "missing argument".to_owned()),
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, rest) = match line.find(char::is_whitespace) {
+            Some(split) => (&line[..split], line[split..].trim()),
+            None => (line, ""),
+        };
+        let args: Vec<&str> = rest.split_whitespace().collect();
+
+        Ok(match name {
+            "s" | "step" => Command::Step(arg(&args, 0, Some(1))?),
+            "c" | "continue" => Command::Continue,
+            "bs" | "back" => Command::Back(arg(&args, 0, Some(1))?),
+            "rewind" => Command::Rewind(arg(&args, 0, None)?),
+            "ro" | "rout" => Command::ReverseOutput,
+            "b" | "break" => Command::Break(arg(&args, 0, None)?),
+            "d" | "delete" => Command::Delete(arg(&args, 0, None)?),
+            "bl" | "breakpoints" => Command::Breakpoints,
+            "w" | "watch" => {
+                let access = match args.get(1).copied().unwrap_or("rw") {
+                    "r" => Access::Read,
+                    "w" => Access::Write,
+                    "rw" => Access::ReadWrite,
+                    e => return Err(format!("expected r, w or rw, found {:?}", e)),
+                };
+                Command::Watch(arg(&args, 0, None)?, access)
+            }
+            "unwatch" => Command::Unwatch(arg(&args, 0, None)?),
+            "x" | "mem" => Command::Memory(arg(&args, 0, None)?, arg(&args, 1, Some(8))?),
+            "poke" => Command::Poke(arg(&args, 0, None)?, arg(&args, 1, None)?),
+            "r" | "regs" => Command::Registers,
+            "l" | "list" => Command::List(arg(&args, 0, Some(5))?),
+            "i" | "input" => {
+                if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+                    let text = rest[1..rest.len() - 1].replace("\\n", "\n");
+                    Command::Input(text.bytes().map(i64::from).collect())
+                } else {
+                    let values = (0..args.len())
+                        .map(|idx| arg(&args, idx, None))
+                        .collect::<Result<_, _>>()?;
+                    Command::Input(values)
+                }
+            }
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            e => return Err(format!("unknown command {:?}, try help", e)),
+        })
+    }
+}
+
+/// Why the debugger handed control back to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+    NeedInput,
+    Halted,
+    Fault(VmError),
+}
+
+pub struct Debugger {
+    vm: Vm,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    /// Takes over `vm`, recording its history from here on so it can be run backwards
+    pub fn new(mut vm: Vm) -> Debugger {
+        vm.record_history(true);
+        Debugger {
+            vm,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Executes one instruction, collecting anything it prints into `outputs`
+    pub fn step(&mut self, outputs: &mut Vec<i64>) -> Stop {
+        let result = self.vm.step();
+        let hits = self.vm.take_watch_hits();
+        // a fault matters more than whatever the instruction touched on the way to it
+        if let Err(e) = result {
+            return Stop::Fault(e);
+        }
+        if let Some(&hit) = hits.first() {
+            if let Ok(Some(State::Output(out))) = result {
+                outputs.push(out);
+            }
+            return Stop::Watchpoint(hit);
+        }
+
+        match result {
+            Ok(None) => Stop::Stepped,
+            Ok(Some(State::Output(out))) => {
+                outputs.push(out);
+                Stop::Stepped
+            }
+            Ok(Some(State::NeedInput)) => Stop::NeedInput,
+            Ok(Some(State::Halted)) => Stop::Halted,
+            Err(e) => Stop::Fault(e),
+        }
+    }
+
+    /// Runs until something interesting happens. A breakpoint on the current instruction
+    /// doesn't count, so that continuing from a breakpoint makes progress.
+    pub fn cont(&mut self, outputs: &mut Vec<i64>) -> Stop {
+        loop {
+            match self.step(outputs) {
+                Stop::Stepped => {}
+                stop => break stop,
+            }
+            if self.breakpoints.contains(&self.vm.ip()) {
+                break Stop::Breakpoint(self.vm.ip());
+            }
+        }
+    }
+
+    fn describe_operand(&self, operand: &Operand) -> Option<String> {
+        let address = match operand.mode {
+            Mode::Immediate => return None,
+            Mode::Position => Some(operand.value),
+            Mode::Relative => self.vm.relative_base().checked_add(operand.value),
+        };
+        let address = match address {
+            Some(address) if address >= 0 => address,
+            _ => return Some(format!("{}=?", operand)),
+        };
+        let value = self.vm.peek(address as usize);
+        Some(match operand.mode {
+            Mode::Relative => format!("{}@{}={}", operand, address, value),
+            _ => format!("{}={}", operand, value),
+        })
+    }
+
+    /// The instruction at `ip`, along with the current values of its memory operands
+    pub fn current_instruction(&self) -> String {
+        let ip = self.vm.ip();
+        match instruction_in(self.vm.memory(), ip) {
+            Some(line) => {
+                let values: Vec<_> = match &line.item {
+                    Item::Code(_, operands) => operands
+                        .iter()
+                        .filter_map(|o| self.describe_operand(o))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                if values.is_empty() {
+                    line.to_string()
+                } else {
+                    format!("{:<40} ; {}", line.to_string(), values.join(", "))
+                }
+            }
+            None => format!("{:04}: ??? {}", ip, self.vm.peek(ip)),
+        }
+    }
+
+    fn report(&self, stop: &Stop, outputs: &[i64]) -> String {
+        let mut report = String::new();
+        if !outputs.is_empty() {
+            let _ = writeln!(report, "output: {:?}", outputs);
+            if outputs.iter().all(|o| (0..128).contains(o)) {
+                let text: String = outputs.iter().map(|&o| o as u8 as char).collect();
+                let _ = writeln!(report, "{}", text.trim_end());
+            }
+        }
+        match stop {
+            Stop::Stepped => {}
+            Stop::Breakpoint(address) => {
+                let _ = writeln!(report, "breakpoint at {}", address);
+            }
+            Stop::Watchpoint(hit) => {
+                let verb = match hit.access {
+                    Access::Read => "read",
+                    _ => "written",
+                };
+                let _ = writeln!(
+                    report,
+                    "[{}] {} by the instruction at {}, value {}",
+                    hit.address, verb, hit.ip, hit.value
+                );
+            }
+            Stop::NeedInput => report.push_str("waiting for input\n"),
+            Stop::Halted => report.push_str("program halted\n"),
+            Stop::Fault(e) => {
+                let _ = writeln!(report, "fault: {}", e);
+            }
+        }
+        report.push_str(&self.current_instruction());
+        report
+    }
+
+    /// Carries out a command and describes what happened
+    pub fn execute(&mut self, command: &Command) -> String {
+        match command {
+            Command::Step(n) => {
+                let mut outputs = Vec::new();
+                let mut stop = Stop::Stepped;
+                for _ in 0..*n {
+                    stop = self.step(&mut outputs);
+                    if stop != Stop::Stepped {
+                        break;
+                    }
+                }
+                self.report(&stop, &outputs)
+            }
+            Command::Continue => {
+                let mut outputs = Vec::new();
+                let stop = self.cont(&mut outputs);
+                self.report(&stop, &outputs)
+            }
+            Command::Back(n) => {
+                let undone = (0..*n).take_while(|_| self.vm.step_back()).count();
+                let mut report = String::new();
+                if undone < *n {
+                    let _ = writeln!(report, "reached the start of the recorded history");
+                }
+                report.push_str(&self.current_instruction());
+                report
+            }
+            Command::Rewind(address) => {
+                if self.vm.rewind_to_write(*address) {
+                    self.current_instruction()
+                } else {
+                    format!("[{}] hasn't been written to", address)
+                }
+            }
+            Command::ReverseOutput => match self.vm.reverse_to_output() {
+                Some(out) => format!("printed {}\n{}", out, self.current_instruction()),
+                None => "nothing has been printed yet".to_owned(),
+            },
+            Command::Break(address) => {
+                self.add_breakpoint(*address);
+                format!("breakpoint set at {}", address)
+            }
+            Command::Delete(address) => {
+                if self.remove_breakpoint(*address) {
+                    format!("breakpoint at {} removed", address)
+                } else {
+                    format!("no breakpoint at {}", address)
+                }
+            }
+            Command::Breakpoints => {
+                if self.breakpoints.is_empty() {
+                    "no breakpoints".to_owned()
+                } else {
+                    let addresses: Vec<_> =
+                        self.breakpoints.iter().map(|b| b.to_string()).collect();
+                    addresses.join(", ")
+                }
+            }
+            Command::Watch(address, access) => {
+                self.vm.watch(*address, *access);
+                format!("watching [{}] ({:?})", address, access)
+            }
+            Command::Unwatch(address) => {
+                self.vm.unwatch(*address);
+                format!("stopped watching [{}]", address)
+            }
+            Command::Memory(address, count) => {
+                let mut dump = String::new();
+                // inclusive, so the very last address can still be looked at
+                let last = address.saturating_add(count.saturating_sub(1));
+                if *count > 0 {
+                    for row in (*address..=last).step_by(8) {
+                        let end = row.saturating_add(7).min(last);
+                        let values: Vec<_> = (row..=end)
+                            .map(|a| format!("{:>6}", self.vm.peek(a)))
+                            .collect();
+                        let _ = writeln!(dump, "{:04}: {}", row, values.join(" "));
+                    }
+                }
+                dump.trim_end().to_owned()
+            }
+            Command::Poke(address, value) => {
+                let old = self.vm.peek(*address);
+                match self.vm.poke(*address, *value) {
+                    Ok(()) => format!("[{}] {} -> {}", address, old, value),
+                    Err(e) => format!("can't write [{}]: {}", address, e),
+                }
+            }
+            Command::Registers => format!(
+                "ip {}  rb {}  steps {}  pending input {:?}",
+                self.vm.ip(),
+                self.vm.relative_base(),
+                self.vm.steps(),
+                self.vm.pending_input()
+            ),
+            Command::List(count) => {
+                let mut lines = Vec::new();
+                let mut address = self.vm.ip();
+                for _ in 0..*count {
+                    match instruction_in(self.vm.memory(), address) {
+                        Some(line) => {
+                            address += line.size();
+                            lines.push(line.to_string());
+                        }
+                        None => {
+                            lines.push(format!("{:04}: ??? {}", address, self.vm.peek(address)));
+                            break;
+                        }
+                    }
+                }
+                lines.join("\n")
+            }
+            Command::Input(values) => {
+                self.vm.input_all(values.iter().copied());
+                format!("queued {} value(s)", values.len())
+            }
+            Command::Help => HELP.to_owned(),
+            Command::Quit => String::new(),
+        }
+    }
+}
+
+/// Reads commands from the terminal until the user quits or stdin closes
+pub fn repl(debugger: &mut Debugger) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    let mut last = Command::Step(1);
+    writeln!(stdout, "{}", debugger.current_instruction())?;
+
+    loop {
+        write!(
+            stdout,
+            "{}(icdb){} ",
+            color::Fg(color::Cyan),
+            color::Fg(color::Reset)
+        )?;
+        stdout.flush()?;
+
+        let line = match stdin.read_line()? {
+            Some(line) => line,
+            None => break,
+        };
+
+        let command = if line.trim().is_empty() {
+            last.clone()
+        } else {
+            match Command::parse(&line) {
+                Ok(command) => command,
+                Err(e) => {
+                    writeln!(
+                        stdout,
+                        "{}{}{}",
+                        color::Fg(color::Red),
+                        e,
+                        color::Fg(color::Reset)
+                    )?;
+                    continue;
+                }
+            }
+        };
+
+        if command == Command::Quit {
+            break;
+        }
+        writeln!(stdout, "{}", debugger.execute(&command))?;
+        last = command;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::asm::assemble;
+    use super::super::Overflow;
+    use super::*;
+
+    fn counter() -> Debugger {
+        let tape = assemble(
+            "
+    loop:   add [count], #1, [count]
+            out [count]
+            lt [count], #3, [more]
+            jnz [more], loop
+            hlt
+    count:  data 0
+    more:   data 0
+            ",
+        )
+        .unwrap();
+        Debugger::new(Vm::new(&tape))
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse("s"), Ok(Command::Step(1)));
+        assert_eq!(Command::parse("step 10"), Ok(Command::Step(10)));
+        assert_eq!(Command::parse("x 100"), Ok(Command::Memory(100, 8)));
+        assert_eq!(Command::parse("poke 4 -7"), Ok(Command::Poke(4, -7)));
+        assert_eq!(
+            Command::parse("input \"NOT A J\\n\"").unwrap(),
+            Command::Input("NOT A J\n".bytes().map(i64::from).collect())
+        );
+        assert_eq!(Command::parse("back 3"), Ok(Command::Back(3)));
+        assert_eq!(Command::parse("ro"), Ok(Command::ReverseOutput));
+        assert!(Command::parse("break").is_err());
+        assert!(Command::parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = counter();
+        debugger.add_breakpoint(4);
+
+        let mut outputs = Vec::new();
+        assert_eq!(debugger.cont(&mut outputs), Stop::Breakpoint(4));
+        assert_eq!(outputs, vec![]);
+        assert_eq!(debugger.cont(&mut outputs), Stop::Breakpoint(4));
+        assert_eq!(outputs, vec![1]);
+
+        debugger.remove_breakpoint(4);
+        assert_eq!(debugger.cont(&mut outputs), Stop::Halted);
+        assert_eq!(outputs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut debugger = counter();
+        debugger.execute(&Command::parse("watch 14 w").unwrap());
+
+        let mut outputs = Vec::new();
+        let expected = WatchHit {
+            ip: 0,
+            address: 14,
+            access: Access::Write,
+            value: 1,
+        };
+        assert_eq!(debugger.cont(&mut outputs), Stop::Watchpoint(expected));
+        assert_eq!(debugger.vm().ip(), 4);
+    }
+
+    #[test]
+    fn time_travel() {
+        let mut debugger = counter();
+        let mut outputs = Vec::new();
+        assert_eq!(debugger.cont(&mut outputs), Stop::Halted);
+        assert_eq!(outputs, vec![1, 2, 3]);
+
+        assert_eq!(
+            debugger.execute(&Command::ReverseOutput),
+            format!("printed 3\n{:<40} ; [14]=3", "0004: out  [14]")
+        );
+        debugger.execute(&Command::Rewind(14));
+        assert_eq!(debugger.vm().ip(), 0);
+        assert_eq!(debugger.vm().peek(14), 2);
+
+        debugger.execute(&Command::Back(100));
+        assert_eq!(debugger.vm().steps(), 0);
+        assert_eq!(debugger.vm().peek(14), 0);
+        outputs.clear();
+        assert_eq!(debugger.cont(&mut outputs), Stop::Halted);
+        assert_eq!(outputs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn inspect_and_patch() {
+        let mut debugger = counter();
+        assert_eq!(
+            debugger.current_instruction(),
+            format!("{:<40} ; [14]=0, [14]=0", "0000: add  [14], #1, [14]")
+        );
+
+        debugger.execute(&Command::Poke(14, 10));
+        assert_eq!(
+            debugger.execute(&Command::Memory(14, 2)),
+            "0014:     10      0"
+        );
+
+        let mut outputs = Vec::new();
+        assert_eq!(debugger.cont(&mut outputs), Stop::Halted);
+        assert_eq!(outputs, vec![11]);
+    }
+
+    #[test]
+    fn faults_beat_watchpoints() {
+        let tape = assemble("add [big], [big], [big]\nhlt\nbig: data 9223372036854775807").unwrap();
+        let mut debugger = Debugger::new(Vm::new(&tape));
+        debugger.vm_mut().set_overflow(Overflow::Fail);
+        debugger.execute(&Command::Watch(5, Access::Read));
+
+        let mut outputs = Vec::new();
+        assert_eq!(
+            debugger.step(&mut outputs),
+            Stop::Fault(VmError::Overflow { ip: 0 })
+        );
+        assert_eq!(
+            debugger.step(&mut outputs),
+            Stop::Fault(VmError::Overflow { ip: 0 })
+        );
+    }
+
+    #[test]
+    fn memory_at_the_top() {
+        let mut debugger = counter();
+        let dump = debugger.execute(&Command::parse("mem 18446744073709551615 8").unwrap());
+        assert_eq!(dump, "18446744073709551615:      0");
+    }
+
+    #[test]
+    fn poke_out_of_reach() {
+        let mut debugger = counter();
+        let reply = debugger.execute(&Command::parse("poke 18446744073709551615 1").unwrap());
+        assert!(reply.starts_with("can't write [18446744073709551615]"));
+        let reply = debugger.execute(&Command::parse("poke 4000000000000 1").unwrap());
+        assert!(reply.starts_with("can't write [4000000000000]"));
+        assert_eq!(
+            debugger.execute(&Command::parse("poke 100 5").unwrap()),
+            "[100] 0 -> 5"
+        );
+    }
+
+    #[test]
+    fn relative_base_at_the_top() {
+        let mut debugger = Debugger::new(Vm::new(&[109, i64::MAX, 204, 5, 99]));
+        debugger.step(&mut Vec::new());
+        assert!(debugger.current_instruction().ends_with("=?"));
+    }
+}
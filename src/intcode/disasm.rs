@@ -0,0 +1,327 @@
+use super::{Instruction, Memory, Mode, Opcode};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Data that runs on longer than this gets split over several lines
+const DATA_PER_LINE: usize = 8;
+/// Shorter runs of printable characters are probably just numbers
+const MIN_TEXT_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb-{}]", -self.value),
+            Mode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Code(Opcode, Vec<Operand>),
+    Data(Vec<i64>),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: usize,
+    pub item: Item,
+}
+
+impl Line {
+    /// How many cells of the tape this line covers
+    pub fn size(&self) -> usize {
+        match &self.item {
+            Item::Code(_, operands) => operands.len() + 1,
+            Item::Data(values) => values.len(),
+            Item::Text(text) => text.chars().count(),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}: ", self.address)?;
+        match &self.item {
+            Item::Code(opcode, operands) if operands.is_empty() => {
+                write!(f, "{}", opcode.mnemonic())
+            }
+            Item::Code(opcode, operands) => {
+                let operands: Vec<_> = operands.iter().map(|o| o.to_string()).collect();
+                write!(f, "{:<4} {}", opcode.mnemonic(), operands.join(", "))
+            }
+            Item::Data(values) => {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "data {}", values.join(", "))
+            }
+            Item::Text(text) => write!(f, "data {:?}", text),
+        }
+    }
+}
+
+fn decode_at(tape: &[i64], address: usize) -> Option<Instruction> {
+    let instr = Instruction::decode(*tape.get(address)?)?;
+    if address + instr.size() > tape.len() {
+        None
+    } else {
+        Some(instr)
+    }
+}
+
+fn operand(tape: &[i64], address: usize, instr: &Instruction, idx: usize) -> Operand {
+    Operand {
+        mode: instr.modes[idx],
+        value: tape[address + idx + 1],
+    }
+}
+
+fn in_range(tape: &[i64], value: i64) -> Option<usize> {
+    if value >= 0 && (value as usize) < tape.len() {
+        Some(value as usize)
+    } else {
+        None
+    }
+}
+
+/// A pointer only counts as code if the first few instructions it leads to make sense
+fn looks_like_code(tape: &[i64], covered: &[bool], mut address: usize) -> bool {
+    for _ in 0..3 {
+        if covered[address] {
+            return false;
+        }
+        let instr = match decode_at(tape, address) {
+            Some(instr) => instr,
+            None => return false,
+        };
+        if instr.opcode == Opcode::Hlt {
+            return true;
+        }
+        address += instr.size();
+        if address >= tape.len() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Follows control flow from address 0 and any immediate jump targets. Intcode programs also
+/// push return addresses with things like `add #ret, #0, [rb+1]`, so constant values that
+/// point at plausible instructions are treated as entry points too. Returns the address of
+/// every instruction that was found.
+fn find_code(tape: &[i64]) -> BTreeSet<usize> {
+    let mut starts = BTreeSet::new();
+    let mut covered = vec![false; tape.len()];
+    let mut queue = vec![0];
+    let mut pointers = Vec::new();
+
+    loop {
+        while let Some(mut address) = queue.pop() {
+            while address < tape.len() && !covered[address] {
+                let instr = match decode_at(tape, address) {
+                    Some(instr) => instr,
+                    None => break,
+                };
+
+                starts.insert(address);
+                for c in &mut covered[address..address + instr.size()] {
+                    *c = true;
+                }
+
+                let param = |idx| operand(tape, address, &instr, idx);
+                match instr.opcode {
+                    Opcode::Hlt => break,
+                    Opcode::Jnz | Opcode::Jz => {
+                        let (cond, target) = (param(0), param(1));
+                        if target.mode == Mode::Immediate {
+                            queue.extend(in_range(tape, target.value));
+                        }
+                        let always = cond.mode == Mode::Immediate
+                            && ((instr.opcode == Opcode::Jnz) == (cond.value != 0));
+                        if always {
+                            break;
+                        }
+                    }
+                    Opcode::Add | Opcode::Mul => {
+                        let (a, b) = (param(0), param(1));
+                        if a.mode == Mode::Immediate && b.mode == Mode::Immediate {
+                            let value = if instr.opcode == Opcode::Add {
+                                a.value.checked_add(b.value)
+                            } else {
+                                a.value.checked_mul(b.value)
+                            };
+                            pointers.extend(value.and_then(|v| in_range(tape, v)));
+                        }
+                    }
+                    _ => {}
+                }
+
+                address += instr.size();
+            }
+        }
+
+        queue.extend(
+            pointers
+                .drain(..)
+                .filter(|&p| looks_like_code(tape, &covered, p)),
+        );
+        if queue.is_empty() {
+            break starts;
+        }
+    }
+}
+
+fn is_text(value: i64) -> bool {
+    value == i64::from(b'\n') || (32..127).contains(&value)
+}
+
+/// Splits a run of data into strings and plain numbers
+fn data_lines(tape: &[i64], start: usize, end: usize, lines: &mut Vec<Line>) {
+    let mut address = start;
+    while address < end {
+        let text_len = tape[address..end]
+            .iter()
+            .take_while(|&&v| is_text(v))
+            .count();
+
+        if text_len >= MIN_TEXT_LEN {
+            let text = tape[address..address + text_len]
+                .iter()
+                .map(|&v| v as u8 as char)
+                .collect();
+            lines.push(Line {
+                address,
+                item: Item::Text(text),
+            });
+            address += text_len;
+            continue;
+        }
+
+        // stop plain data at the start of the next string
+        let mut data_end = address + 1;
+        while data_end < end && data_end - address < DATA_PER_LINE {
+            let upcoming = tape[data_end..end]
+                .iter()
+                .take_while(|&&v| is_text(v))
+                .count();
+            if upcoming >= MIN_TEXT_LEN {
+                break;
+            }
+            data_end += 1;
+        }
+        lines.push(Line {
+            address,
+            item: Item::Data(tape[address..data_end].to_vec()),
+        });
+        address = data_end;
+    }
+}
+
+/// Decodes the instruction at `address`, or `None` if there isn't a valid one there
+pub fn instruction_at(tape: &[i64], address: usize) -> Option<Line> {
+    let instr = decode_at(tape, address)?;
+    let operands = (0..instr.opcode.param_count())
+        .map(|idx| operand(tape, address, &instr, idx))
+        .collect();
+    Some(Line {
+        address,
+        item: Item::Code(instr.opcode, operands),
+    })
+}
+
+/// Like `instruction_at`, but for a running machine's memory
+pub fn instruction_in(memory: &Memory, address: usize) -> Option<Line> {
+    // no instruction is longer than 4 cells
+    let end = memory.len().min(address + 4);
+    let window: Vec<_> = (address..end).map(|a| memory[a]).collect();
+    let mut line = instruction_at(&window, 0)?;
+    line.address = address;
+    Some(line)
+}
+
+pub fn disassemble(tape: &[i64]) -> Vec<Line> {
+    let starts = find_code(tape);
+    let mut lines = Vec::new();
+
+    let mut address = 0;
+    while address < tape.len() {
+        if starts.contains(&address) {
+            let line = instruction_at(tape, address).unwrap();
+            address += line.size();
+            lines.push(line);
+        } else {
+            let end = starts
+                .range(address..)
+                .next()
+                .copied()
+                .unwrap_or(tape.len());
+            data_lines(tape, address, end, &mut lines);
+            address = end;
+        }
+    }
+
+    lines
+}
+
+/// The whole program as text, one line per instruction or run of data
+pub fn listing(tape: &[i64]) -> String {
+    disassemble(tape)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quine() {
+        let tape = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(
+            listing(&tape),
+            "0000: arb  #1
+0002: out  [rb-1]
+0004: add  [100], #1, [100]
+0008: eq   [100], #16, [101]
+0012: jz   [101], #0
+0015: hlt
+"
+        );
+    }
+
+    #[test]
+    fn data_and_text() {
+        let mut tape = vec![1105, 1, 9, 7, -3, 500, 0, 0, 0, 99];
+        tape.extend("Hello!\n".bytes().map(i64::from));
+
+        let lines = disassemble(&tape);
+        assert_eq!(lines[1].item, Item::Data(vec![7, -3, 500, 0, 0, 0]));
+        assert_eq!(lines[2].item, Item::Code(Opcode::Hlt, vec![]));
+        assert_eq!(lines[3].item, Item::Text("Hello!\n".to_owned()));
+        assert_eq!(lines[3].to_string(), r#"0010: data "Hello!\n""#);
+    }
+
+    #[test]
+    fn return_address() {
+        // calls the function at 10 with the return address 7 pushed onto the stack
+        let tape = vec![21101, 7, 0, 0, 1105, 1, 10, 104, 1, 99, 104, 2, 2106, 0, 0];
+        let lines = disassemble(&tape);
+        let code: Vec<_> = lines
+            .iter()
+            .filter(|l| matches!(l.item, Item::Code(..)))
+            .map(|l| l.address)
+            .collect();
+        assert_eq!(code, vec![0, 4, 7, 9, 10, 12]);
+    }
+}
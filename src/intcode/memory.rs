@@ -0,0 +1,217 @@
+use super::Cell;
+use std::collections::BTreeMap;
+use std::ops::Index;
+use std::sync::Arc;
+
+/// How many cells each page of paged memory holds
+pub const PAGE_SIZE: usize = 1024;
+
+#[derive(Debug, Clone)]
+enum Repr<C> {
+    Flat(Vec<C>),
+    /// Pages are shared between copies until one of them writes, so cloning is cheap too
+    Paged {
+        pages: BTreeMap<usize, Arc<Vec<C>>>,
+        len: usize,
+    },
+}
+
+/// An Intcode machine's memory. Every address reads as 0 until something is written there.
+/// Flat memory is one `Vec` reaching up to the highest address used, which is the fastest
+/// for normal programs. Paged memory only allocates the pages that have been written, so a
+/// program that writes to address 1,000,000,000 doesn't need gigabytes for it.
+#[derive(Debug, Clone)]
+pub struct Memory<C = i64> {
+    repr: Repr<C>,
+    /// What unwritten addresses read as
+    zero: C,
+}
+
+impl<C: Cell> Memory<C> {
+    pub fn flat(tape: &[C]) -> Memory<C> {
+        Memory {
+            repr: Repr::Flat(tape.to_owned()),
+            zero: C::zero(),
+        }
+    }
+
+    pub fn paged(tape: &[C]) -> Memory<C> {
+        let mut memory = Memory {
+            repr: Repr::Paged {
+                pages: BTreeMap::new(),
+                len: 0,
+            },
+            zero: C::zero(),
+        };
+        for (address, value) in tape.iter().enumerate() {
+            memory.set(address, value.clone());
+        }
+        memory
+    }
+
+    pub fn is_paged(&self) -> bool {
+        match self.repr {
+            Repr::Flat(_) => false,
+            Repr::Paged { .. } => true,
+        }
+    }
+
+    /// One past the highest address that has been loaded or written
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Flat(tape) => tape.len(),
+            Repr::Paged { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, address: usize) -> C {
+        self[address].clone()
+    }
+
+    pub fn set(&mut self, address: usize, value: C) {
+        match &mut self.repr {
+            Repr::Flat(tape) => {
+                if address >= tape.len() {
+                    tape.resize(address + 1, C::zero());
+                }
+                tape[address] = value;
+            }
+            Repr::Paged { pages, len } => {
+                let page = pages
+                    .entry(address / PAGE_SIZE)
+                    .or_insert_with(|| Arc::new(vec![C::zero(); PAGE_SIZE]));
+                Arc::make_mut(page)[address % PAGE_SIZE] = value;
+                *len = (*len).max(address + 1);
+            }
+        }
+    }
+
+    /// Forgets everything from `len` onwards, as if it had never been written
+    pub fn truncate(&mut self, new_len: usize) {
+        match &mut self.repr {
+            Repr::Flat(tape) => tape.truncate(new_len),
+            Repr::Paged { pages, len } => {
+                if new_len >= *len {
+                    return;
+                }
+                pages.split_off(&new_len.div_ceil(PAGE_SIZE));
+                if let Some(page) = pages.get_mut(&(new_len / PAGE_SIZE)) {
+                    for cell in &mut Arc::make_mut(page)[new_len % PAGE_SIZE..] {
+                        *cell = C::zero();
+                    }
+                }
+                *len = new_len;
+            }
+        }
+    }
+
+    /// The memory in pieces, each with the address it starts at. Addresses that aren't
+    /// covered by any of them are 0.
+    pub fn chunks(&self) -> Vec<(usize, &[C])> {
+        match &self.repr {
+            Repr::Flat(tape) => vec![(0, &tape[..])],
+            Repr::Paged { pages, len } => pages
+                .iter()
+                .map(|(&idx, page)| {
+                    let start = idx * PAGE_SIZE;
+                    (start, &page[..PAGE_SIZE.min(len - start)])
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<C> {
+        match &self.repr {
+            Repr::Flat(tape) => tape.clone(),
+            Repr::Paged { .. } => (0..self.len()).map(|address| self.get(address)).collect(),
+        }
+    }
+}
+
+impl<C> Index<usize> for Memory<C> {
+    type Output = C;
+
+    fn index(&self, address: usize) -> &C {
+        match &self.repr {
+            Repr::Flat(tape) => tape.get(address).unwrap_or(&self.zero),
+            Repr::Paged { pages, .. } => match pages.get(&(address / PAGE_SIZE)) {
+                Some(page) => &page[address % PAGE_SIZE],
+                None => &self.zero,
+            },
+        }
+    }
+}
+
+/// Two memories are equal if they're the same kind and every address holds the same value
+impl<C: Cell> PartialEq for Memory<C> {
+    fn eq(&self, other: &Memory<C>) -> bool {
+        match (&self.repr, &other.repr) {
+            (Repr::Flat(a), Repr::Flat(b)) => a == b,
+            (
+                Repr::Paged {
+                    pages: a,
+                    len: a_len,
+                },
+                Repr::Paged {
+                    pages: b,
+                    len: b_len,
+                },
+            ) => {
+                a_len == b_len
+                    && a.keys()
+                        .chain(b.keys())
+                        .all(|idx| match (a.get(idx), b.get(idx)) {
+                            (Some(a), Some(b)) => a == b,
+                            (Some(page), None) | (None, Some(page)) => {
+                                page.iter().all(|cell| *cell == self.zero)
+                            }
+                            (None, None) => true,
+                        })
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<C: Cell> Eq for Memory<C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paged_matches_flat() {
+        let tape = vec![1, 2, 3, 4];
+        let mut flat = Memory::flat(&tape);
+        let mut paged = Memory::paged(&tape);
+        for &(address, value) in &[(2, 30), (5000, -1), (1023, 7), (1024, 8)] {
+            flat.set(address, value);
+            paged.set(address, value);
+        }
+
+        assert_eq!(paged.len(), 5001);
+        assert_eq!(paged.to_vec(), flat.to_vec());
+        assert_eq!(paged[1_000_000], 0);
+        // only the pages that were written to exist
+        let starts: Vec<_> = paged.chunks().iter().map(|&(start, _)| start).collect();
+        assert_eq!(starts, vec![0, 1024, 4096]);
+    }
+
+    #[test]
+    fn truncate() {
+        let mut paged = Memory::paged(&[1, 2, 3]);
+        let before = paged.clone();
+        paged.set(2000, 9);
+        paged.set(1, 0);
+        assert_ne!(paged, before);
+
+        paged.set(1, 2);
+        paged.truncate(3);
+        assert_eq!(paged, before);
+        assert_eq!(paged[2000], 0);
+    }
+}
@@ -0,0 +1,387 @@
+use super::{MachineError, State, Vm, VmError};
+use std::collections::{HashMap, VecDeque};
+
+/// What a node reads when there's no packet waiting for it
+const NO_PACKET: i64 = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packet {
+    pub from: i64,
+    pub to: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// A packet in the log, along with when it was sent. Time is counted in instructions
+/// executed across the whole network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Captured {
+    pub step: u64,
+    pub packet: Packet,
+}
+
+/// How the network decides which node runs next. Every policy is deterministic, so the
+/// same program and policy always produce the same packet log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Nodes take turns, each running until it sends a packet or has to wait for one
+    RoundRobin,
+    /// Nodes take turns, each running until it has to wait for a packet
+    UntilBlocked,
+    /// Like `RoundRobin`, but the next node is picked at random from this seed
+    Random(u64),
+}
+
+/// Something other than a node living at an address, like the puzzle's NAT
+pub trait Device {
+    /// Called with every packet sent to the device's address
+    fn receive(&mut self, packet: Packet);
+
+    /// Called when the whole network has gone idle. Any packets returned are sent from the
+    /// device's address, whatever their `from` says.
+    fn idle(&mut self) -> Vec<Packet> {
+        Vec::new()
+    }
+}
+
+/// Remembers the last packet it was sent, and sends it on to address 0 whenever the network
+/// goes idle
+#[derive(Debug, Clone, Default)]
+pub struct Nat {
+    last: Option<Packet>,
+}
+
+impl Nat {
+    pub fn new() -> Nat {
+        Nat::default()
+    }
+}
+
+impl Device for Nat {
+    fn receive(&mut self, packet: Packet) {
+        self.last = Some(packet);
+    }
+
+    fn idle(&mut self) -> Vec<Packet> {
+        match self.last {
+            Some(packet) => vec![Packet { to: 0, ..packet }],
+            None => Vec::new(),
+        }
+    }
+}
+
+struct Node {
+    vm: Vm,
+    queue: VecDeque<(i64, i64)>,
+    /// Outputs that don't add up to a whole packet yet
+    partial: Vec<i64>,
+    /// How many times in a row it's asked for a packet when there wasn't one, without
+    /// sending anything in between
+    empty_polls: u32,
+    halted: bool,
+}
+
+impl Node {
+    /// The first time a node is told there's nothing for it, it might still be busy with
+    /// the last packet. Only once it's come back asking again without sending anything can
+    /// we be sure it's got nothing to do.
+    fn is_idle(&self) -> bool {
+        self.halted || (self.queue.is_empty() && self.empty_polls >= 2)
+    }
+}
+
+/// A packet-switched network of Intcode nodes. Each node boots with its address as its
+/// first input, then sends packets as three outputs (address, x, y) and receives them as
+/// two inputs, reading -1 when nothing has arrived.
+pub struct Network {
+    nodes: Vec<Node>,
+    devices: HashMap<i64, Box<dyn Device>>,
+    schedule: Schedule,
+    /// The next node to run, for the schedules that take turns
+    cursor: usize,
+    rng: u64,
+    /// Instructions executed so far, by every node put together
+    steps: u64,
+    log: Vec<Captured>,
+}
+
+impl Network {
+    pub fn new(tape: &[i64], nodes: usize, schedule: Schedule) -> Network {
+        let nodes = (0..nodes)
+            .map(|address| {
+                let mut vm = Vm::new(tape);
+                vm.input(address as i64);
+                Node {
+                    vm,
+                    queue: VecDeque::new(),
+                    partial: Vec::new(),
+                    empty_polls: 0,
+                    halted: false,
+                }
+            })
+            .collect();
+        let rng = match schedule {
+            // xorshift gets stuck at 0
+            Schedule::Random(seed) => seed.max(1),
+            _ => 0,
+        };
+
+        Network {
+            nodes,
+            devices: HashMap::new(),
+            schedule,
+            cursor: 0,
+            rng,
+            steps: 0,
+            log: Vec::new(),
+        }
+    }
+
+    /// Puts `device` at `address`. Packets sent there go to it, not to any node.
+    pub fn attach<D: Device + 'static>(&mut self, address: i64, device: D) {
+        self.devices.insert(address, Box::new(device));
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, address: usize) -> &Vm {
+        &self.nodes[address].vm
+    }
+
+    /// Every packet that's been sent so far, in order, including ones sent by devices and
+    /// ones sent to addresses where nothing lives
+    pub fn log(&self) -> &[Captured] {
+        &self.log
+    }
+
+    /// Instructions executed so far, by every node put together
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Whether every node is waiting for a packet that nobody is going to send
+    pub fn is_idle(&self) -> bool {
+        self.nodes.iter().all(Node::is_idle)
+    }
+
+    /// Sends a packet, as if it came from `packet.from`
+    pub fn send(&mut self, packet: Packet) {
+        let step = self.steps;
+        self.deliver(Captured { step, packet });
+    }
+
+    fn deliver(&mut self, captured: Captured) {
+        let packet = captured.packet;
+        self.log.push(captured);
+        if let Some(device) = self.devices.get_mut(&packet.to) {
+            device.receive(packet);
+        } else if packet.to >= 0 && (packet.to as usize) < self.nodes.len() {
+            let node = &mut self.nodes[packet.to as usize];
+            node.queue.push_back((packet.x, packet.y));
+            node.empty_polls = 0;
+        }
+    }
+
+    /// Runs until a packet is sent that `done` accepts, returning it. Returns `None` if the
+    /// network goes idle and none of the devices have anything to send.
+    pub fn run_until<F>(&mut self, mut done: F) -> Result<Option<Packet>, MachineError>
+    where
+        F: FnMut(&Packet) -> bool,
+    {
+        loop {
+            let sent = if self.nodes.iter().all(|node| node.halted) {
+                return Ok(None);
+            } else if self.is_idle() {
+                let woken = self.wake_devices();
+                if woken.is_empty() {
+                    return Ok(None);
+                }
+                let step = self.steps;
+                woken
+                    .into_iter()
+                    .map(|packet| Captured { step, packet })
+                    .collect()
+            } else {
+                let address = self.next_node();
+                self.run_node(address).map_err(|error| MachineError {
+                    machine: address,
+                    error,
+                })?
+            };
+
+            let mut found = None;
+            for captured in sent {
+                self.deliver(captured);
+                if found.is_none() && done(&captured.packet) {
+                    found = Some(captured.packet);
+                }
+            }
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+    }
+
+    fn wake_devices(&mut self) -> Vec<Packet> {
+        // sorted so the order doesn't depend on the hash map
+        let mut addresses: Vec<_> = self.devices.keys().copied().collect();
+        addresses.sort_unstable();
+
+        let mut woken = Vec::new();
+        for address in addresses {
+            let device = self.devices.get_mut(&address).unwrap();
+            woken.extend(device.idle().into_iter().map(|packet| Packet {
+                from: address,
+                ..packet
+            }));
+        }
+        woken
+    }
+
+    fn next_node(&mut self) -> usize {
+        loop {
+            let address = match self.schedule {
+                Schedule::RoundRobin | Schedule::UntilBlocked => {
+                    let address = self.cursor;
+                    self.cursor = (self.cursor + 1) % self.nodes.len();
+                    address
+                }
+                Schedule::Random(_) => {
+                    self.rng ^= self.rng << 13;
+                    self.rng ^= self.rng >> 7;
+                    self.rng ^= self.rng << 17;
+                    (self.rng % self.nodes.len() as u64) as usize
+                }
+            };
+            if !self.nodes[address].halted {
+                break address;
+            }
+        }
+    }
+
+    /// Gives a node its turn, returning the packets it sent
+    fn run_node(&mut self, address: usize) -> Result<Vec<Captured>, VmError> {
+        let one_packet = self.schedule != Schedule::UntilBlocked;
+        let node = &mut self.nodes[address];
+        let started = node.vm.steps();
+        let mut sent = Vec::new();
+
+        loop {
+            match node.vm.run()? {
+                State::Output(value) => {
+                    node.partial.push(value);
+                    if let [to, x, y] = node.partial[..] {
+                        node.partial.clear();
+                        node.empty_polls = 0;
+                        sent.push(Captured {
+                            step: self.steps + node.vm.steps() - started,
+                            packet: Packet {
+                                from: address as i64,
+                                to,
+                                x,
+                                y,
+                            },
+                        });
+                        if one_packet {
+                            break;
+                        }
+                    }
+                }
+                State::NeedInput => match node.queue.pop_front() {
+                    Some((x, y)) => node.vm.input_all(vec![x, y]),
+                    None => {
+                        node.vm.input(NO_PACKET);
+                        node.empty_polls += 1;
+                        break;
+                    }
+                },
+                State::Halted => {
+                    node.halted = true;
+                    break;
+                }
+            }
+        }
+        self.steps += node.vm.steps() - started;
+        Ok(sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::asm::assemble;
+    use super::*;
+
+    // node 0 sends (1, 7, 0), and every node passes what it gets on to the next address
+    // with y one higher
+    fn relay() -> Vec<i64> {
+        assemble(
+            "
+            in [me]
+            jnz [me], listen
+            out #1
+            out #7
+            out #0
+    listen: in [x]
+            eq [x], #-1, [empty]
+            jnz [empty], listen
+            in [y]
+            add [me], #1, [to]
+            add [y], #1, [y]
+            out [to]
+            out [x]
+            out [y]
+            jz #0, listen
+    me:     data 0
+    x:      data 0
+    y:      data 0
+    to:     data 0
+    empty:  data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    fn packet(from: i64, to: i64, y: i64) -> Packet {
+        Packet { from, to, x: 7, y }
+    }
+
+    #[test]
+    fn idle() {
+        let schedules = [
+            Schedule::RoundRobin,
+            Schedule::UntilBlocked,
+            Schedule::Random(42),
+        ];
+        for &schedule in &schedules {
+            // the last node sends to an address where nothing lives, so it all stops there
+            let mut net = Network::new(&relay(), 3, schedule);
+            assert_eq!(net.run_until(|_| false), Ok(None));
+            assert!(net.is_idle());
+            let packets: Vec<_> = net.log().iter().map(|c| c.packet).collect();
+            assert_eq!(
+                packets,
+                vec![packet(0, 1, 0), packet(1, 2, 1), packet(2, 3, 2)]
+            );
+        }
+    }
+
+    #[test]
+    fn nat() {
+        let mut net = Network::new(&relay(), 3, Schedule::Random(7));
+        net.attach(3, Nat::new());
+        assert_eq!(
+            net.run_until(|p| p.from == 3 && p.y == 5),
+            Ok(Some(packet(3, 0, 5)))
+        );
+        assert_eq!(net.log().len(), 8);
+        assert_eq!(net.log()[3].packet, packet(3, 0, 2));
+        // the NAT only sends once the nodes have gone back to asking for packets
+        assert!(net.log().windows(2).all(|w| w[0].step < w[1].step));
+    }
+}
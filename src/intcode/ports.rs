@@ -0,0 +1,171 @@
+use super::{Cell, State, Vm, VmError};
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// Somewhere a machine can get its input from
+pub trait Input<C = i64> {
+    /// The next value, or `None` if there isn't one. For ports that can block, like
+    /// channels, `None` means there never will be.
+    fn read(&mut self) -> Option<C>;
+}
+
+/// Somewhere a machine can send its output
+pub trait Output<C = i64> {
+    fn write(&mut self, value: C);
+}
+
+/// A port backed by a closure: `FnMut() -> Option<C>` for input, `FnMut(C)` for output
+pub struct FromFn<F>(pub F);
+
+impl<C, F: FnMut() -> Option<C>> Input<C> for FromFn<F> {
+    fn read(&mut self) -> Option<C> {
+        (self.0)()
+    }
+}
+
+impl<C, F: FnMut(C)> Output<C> for FromFn<F> {
+    fn write(&mut self, value: C) {
+        (self.0)(value)
+    }
+}
+
+impl<C> Input<C> for VecDeque<C> {
+    fn read(&mut self) -> Option<C> {
+        self.pop_front()
+    }
+}
+
+impl<C> Output<C> for VecDeque<C> {
+    fn write(&mut self, value: C) {
+        self.push_back(value);
+    }
+}
+
+impl<C> Output<C> for Vec<C> {
+    fn write(&mut self, value: C) {
+        self.push(value);
+    }
+}
+
+/// Blocks until a value arrives, and runs dry once every sender has gone away
+impl<C> Input<C> for Receiver<C> {
+    fn read(&mut self) -> Option<C> {
+        self.recv().ok()
+    }
+}
+
+// if the other end has hung up, nobody's listening and the output can just be dropped
+impl<C> Output<C> for Sender<C> {
+    fn write(&mut self, value: C) {
+        let _ = self.send(value);
+    }
+}
+
+impl<C> Output<C> for SyncSender<C> {
+    fn write(&mut self, value: C) {
+        let _ = self.send(value);
+    }
+}
+
+impl<C, T: Input<C> + ?Sized> Input<C> for &mut T {
+    fn read(&mut self) -> Option<C> {
+        (**self).read()
+    }
+}
+
+impl<C, T: Output<C> + ?Sized> Output<C> for &mut T {
+    fn write(&mut self, value: C) {
+        (**self).write(value)
+    }
+}
+
+impl<C: Cell> Vm<C> {
+    /// Runs with input coming from `input` and every output going to `output`, until the
+    /// program halts or `input` has nothing left to give it. Anything already queued with
+    /// `Vm::input` gets used first.
+    pub fn run_ports<I, O>(&mut self, mut input: I, mut output: O) -> Result<State<C>, VmError>
+    where
+        I: Input<C>,
+        O: Output<C>,
+    {
+        loop {
+            match self.run()? {
+                State::Output(out) => output.write(out),
+                State::NeedInput => match input.read() {
+                    Some(value) => self.input(value),
+                    None => break Ok(State::NeedInput),
+                },
+                State::Halted => break Ok(State::Halted),
+            }
+        }
+    }
+
+    /// Like `run_ports`, but on a thread of its own. The machine is handed back once it
+    /// stops, so it can be inspected or carry on from there.
+    pub fn spawn<I, O>(mut self, input: I, output: O) -> JoinHandle<Result<Vm<C>, VmError>>
+    where
+        I: Input<C> + Send + 'static,
+        O: Output<C> + Send + 'static,
+    {
+        thread::spawn(move || {
+            self.run_ports(input, output)?;
+            Ok(self)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    // doubles every input until it gets a 0
+    const DOUBLER: [i64; 16] = [
+        3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+    ];
+
+    #[test]
+    fn queues_and_closures() {
+        let mut input: VecDeque<_> = vec![1, 2, 3].into();
+        let mut output = Vec::new();
+        let mut vm = Vm::new(&DOUBLER);
+        assert_eq!(vm.run_ports(&mut input, &mut output), Ok(State::NeedInput));
+        assert_eq!(output, vec![2, 4, 6]);
+
+        let mut next = 10;
+        let mut total = 0;
+        let input = FromFn(|| {
+            next -= 5;
+            Some(next)
+        });
+        assert_eq!(
+            vm.run_ports(input, FromFn(|out| total += out)),
+            Ok(State::Halted)
+        );
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn channels() {
+        // three doublers in a row, each on its own thread
+        let (send, mut recv) = channel();
+        let mut handles = Vec::new();
+        for _ in 0..3 {
+            let (next_send, next_recv) = channel();
+            handles.push(Vm::new(&DOUBLER).spawn(recv, next_send));
+            recv = next_recv;
+        }
+
+        for value in 1..=4 {
+            send.send(value).unwrap();
+        }
+        // hanging up stops the first machine, which hangs up on the second, and so on
+        drop(send);
+        let outputs: Vec<_> = recv.iter().collect();
+        assert_eq!(outputs, vec![8, 16, 24, 32]);
+        for handle in handles {
+            assert!(handle.join().unwrap().is_ok());
+        }
+    }
+}
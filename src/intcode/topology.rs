@@ -0,0 +1,194 @@
+use super::{Cell, State, Vm, VmError};
+use std::error::Error;
+use std::fmt;
+
+/// A fault in one of the machines of a `Topology`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineError {
+    pub machine: usize,
+    pub error: VmError,
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.machine, self.error)
+    }
+}
+
+impl Error for MachineError {}
+
+/// A set of machines with their outputs wired to each other's inputs. Every output goes to
+/// each machine its producer is linked to, so a machine linked to several others fans out.
+pub struct Topology<C: Cell = i64> {
+    machines: Vec<Vm<C>>,
+    /// Where each machine's outputs go
+    links: Vec<Vec<usize>>,
+    outputs: Vec<Vec<C>>,
+    halted: Vec<bool>,
+}
+
+impl<C: Cell> Topology<C> {
+    pub fn new() -> Topology<C> {
+        Topology {
+            machines: Vec::new(),
+            links: Vec::new(),
+            outputs: Vec::new(),
+            halted: Vec::new(),
+        }
+    }
+
+    /// Adds a machine with `inputs` already queued up, returning its id
+    pub fn add<I: IntoIterator<Item = C>>(&mut self, mut vm: Vm<C>, inputs: I) -> usize {
+        vm.input_all(inputs);
+        self.machines.push(vm);
+        self.links.push(Vec::new());
+        self.outputs.push(Vec::new());
+        self.halted.push(false);
+        self.machines.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn link(&mut self, from: usize, to: usize) {
+        self.links[from].push(to);
+    }
+
+    /// Links each machine to the next one
+    pub fn chain(&mut self, ids: &[usize]) {
+        for pair in ids.windows(2) {
+            self.link(pair[0], pair[1]);
+        }
+    }
+
+    /// A chain whose last machine feeds back into the first
+    pub fn ring(&mut self, ids: &[usize]) {
+        self.chain(ids);
+        if let (Some(&first), Some(&last)) = (ids.first(), ids.last()) {
+            self.link(last, first);
+        }
+    }
+
+    pub fn fan_out(&mut self, from: usize, to: &[usize]) {
+        for &id in to {
+            self.link(from, id);
+        }
+    }
+
+    pub fn input(&mut self, id: usize, value: C) {
+        self.machines[id].input(value);
+    }
+
+    /// Runs every machine in turn, passing outputs along, until they've all halted or are
+    /// waiting on input that nothing is going to send. Can be called again after more input
+    /// has been given.
+    pub fn run(&mut self) -> Result<(), MachineError> {
+        loop {
+            let mut progressed = false;
+            for id in 0..self.machines.len() {
+                if self.halted[id] {
+                    continue;
+                }
+                let steps = self.machines[id].steps();
+                self.run_machine(id)
+                    .map_err(|error| MachineError { machine: id, error })?;
+                progressed |= self.machines[id].steps() != steps;
+            }
+            if !progressed {
+                break Ok(());
+            }
+        }
+    }
+
+    fn run_machine(&mut self, id: usize) -> Result<(), VmError> {
+        loop {
+            match self.machines[id].run()? {
+                State::Output(out) => {
+                    for &to in &self.links[id] {
+                        self.machines[to].input(out.clone());
+                    }
+                    self.outputs[id].push(out);
+                }
+                State::NeedInput => break Ok(()),
+                State::Halted => {
+                    self.halted[id] = true;
+                    break Ok(());
+                }
+            }
+        }
+    }
+
+    /// Everything machine `id` has output so far, whether or not it was passed on
+    pub fn outputs(&self, id: usize) -> &[C] {
+        &self.outputs[id]
+    }
+
+    pub fn last_output(&self, id: usize) -> Option<&C> {
+        self.outputs[id].last()
+    }
+
+    pub fn halted(&self, id: usize) -> bool {
+        self.halted[id]
+    }
+
+    pub fn machine(&self, id: usize) -> &Vm<C> {
+        &self.machines[id]
+    }
+}
+
+impl<C: Cell> Default for Topology<C> {
+    fn default() -> Topology<C> {
+        Topology::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // adds its first input to everything after it
+    const OFFSET: [i64; 15] = [3, 13, 3, 14, 1, 14, 13, 14, 4, 14, 1105, 1, 2, 0, 0];
+
+    #[test]
+    fn fan_out() {
+        let mut net = Topology::new();
+        let source = net.add(Vm::new(&OFFSET), vec![100]);
+        let left = net.add(Vm::new(&OFFSET), vec![10]);
+        let right = net.add(Vm::new(&OFFSET), vec![20]);
+        net.fan_out(source, &[left, right]);
+
+        net.input(source, 1);
+        net.input(source, 2);
+        net.run().unwrap();
+        assert_eq!(net.outputs(source), &[101, 102]);
+        assert_eq!(net.outputs(left), &[111, 112]);
+        assert_eq!(net.outputs(right), &[121, 122]);
+        assert!(!net.halted(left));
+
+        // everything's waiting, so more input picks up where it left off
+        net.input(source, 3);
+        net.run().unwrap();
+        assert_eq!(net.last_output(right), Some(&123));
+    }
+
+    #[test]
+    fn faults() {
+        let mut net = Topology::new();
+        let first = net.add(Vm::new(&OFFSET), vec![1]);
+        let broken = net.add(Vm::new(&[3, 0, 42]), vec![]);
+        net.chain(&[first, broken]);
+        net.input(first, 1);
+        assert_eq!(
+            net.run(),
+            Err(MachineError {
+                machine: broken,
+                error: VmError::InvalidOpcode { ip: 2, opcode: 42 }
+            })
+        );
+    }
+}